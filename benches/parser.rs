@@ -0,0 +1,105 @@
+use ansi_parser::{AnsiParser, Output};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sl_up::{
+    glyphs::GlyphSet,
+    graph::{Commit, CommitState, Glyph, Item, ItemType},
+    parser::{has_line_selection_coloring, parsed_line_to_string_vec},
+    smartlog::SmartLog,
+};
+
+/// A stack of two draft commits on a public one, as `sl smartlog --all` repeats it.
+const STACK: [&str; 9] = [
+    "│ o  \u{1b}[0;93;1mc3bd9e5fa\u{1b}[0m  Dec 08 at 09:46  royrothenberg  \u{1b}[0;38;2;141;148;158m#779 Unreviewed\u{1b}[0m \u{1b}[0;31m✗\u{1b}[0m",
+    "│ │  [pr body update] fix reviewstack option breaking stack list detection",
+    "│ │",
+    "│ o  \u{1b}[0;93;1m2f85065e7\u{1b}[0m  Nov 28 at 11:49  royrothenberg  \u{1b}[0;36m#781 Closed\u{1b}[0m \u{1b}[0;32m✓\u{1b}[0m",
+    "├─╯  [isl] increase width of diff window in split stack edit panel",
+    "│",
+    "o  \u{1b}[0;33mba27d4d13\u{1b}[0m  Dec 07 at 22:20  \u{1b}[0;32mremote/main\u{1b}[0m",
+    "╷",
+    "╷",
+];
+
+fn smartlog_lines(count: usize) -> Vec<String> {
+    let mut lines = vec![
+        "  @  \u{1b}[0;35m\u{1b}[0;93;1m1cee5d55e\u{1b}[0m\u{1b}[0;35m  Dec 08 at 09:46  royrothenberg\u{1b}[0m".to_string(),
+        "╭─╯  \u{1b}[0;35m[pr body update] update stack list\u{1b}[0m".to_string(),
+    ];
+    lines.extend(
+        STACK
+            .iter()
+            .cycle()
+            .take(count)
+            .map(|line| line.to_string()),
+    );
+    lines.push("~".to_string());
+    lines
+}
+
+/// The parser before it streamed, as a baseline: the renderer is guessed from every line
+/// first, and the blank lines within a commit's description are moved from the glyphs they
+/// were taken for once its text goes on.
+fn parse_whole(raw_lines: &[String]) -> Vec<ItemType> {
+    let mut items: Vec<ItemType> = Vec::new();
+    let glyphs = GlyphSet::detect(raw_lines);
+    let mut parsed_lines: Vec<Vec<Output>> =
+        raw_lines.iter().map(|x| x.ansi_parse().collect()).collect();
+
+    while !parsed_lines.is_empty() {
+        let mut line = parsed_lines.remove(0);
+        if let [Output::TextBlock(text)] = line.as_slice() {
+            let (graph, text) = glyphs.split_graph(text);
+            line = vec![Output::TextBlock(graph), Output::TextBlock(text)];
+        }
+
+        let text: String = line
+            .iter()
+            .filter_map(|block| match block {
+                Output::TextBlock(text) => Some(*text),
+                _ => None,
+            })
+            .collect();
+        let (graph, text) = glyphs.split_graph(&text);
+        let last_commit = items
+            .iter()
+            .rposition(|item| matches!(item, ItemType::Commit(_)));
+        if let Some(node) = glyphs.node_in(graph) {
+            let selected = has_line_selection_coloring(&line);
+            let commit = Commit::new(vec![parsed_line_to_string_vec(&line)], selected)
+                .with_state(CommitState::from_node(node));
+            items.push(commit.into());
+        } else if let (false, Some(last_commit)) = (text.trim().is_empty(), last_commit) {
+            let blank_lines: Vec<Vec<String>> = items
+                .drain(last_commit + 1..)
+                .flat_map(|item| item.parsed_lines().clone())
+                .collect();
+            let commit = &mut items[last_commit];
+            for blank_line in blank_lines {
+                commit.add_parsed_line(blank_line);
+            }
+            commit.add_parsed_line(parsed_line_to_string_vec(&line));
+        } else {
+            items.push(Glyph::new(vec![parsed_line_to_string_vec(&line)]).into());
+        }
+    }
+    items
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    for count in [1_000, 10_000, 50_000] {
+        let lines = smartlog_lines(count);
+        group.throughput(Throughput::Elements(lines.len() as u64));
+        group.bench_with_input(BenchmarkId::new("streaming", count), &lines, |b, lines| {
+            b.iter(|| SmartLog::new(lines))
+        });
+        group.bench_with_input(BenchmarkId::new("whole", count), &lines, |b, lines| {
+            b.iter(|| SmartLog::from_items(parse_whole(lines)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,128 @@
+//! Local and remote bookmarks, as listed by `sl log` on the commits they point to.
+//!
+/// Separates the fields of a commit in the template output.
+const FIELD_SEPARATOR: char = '\u{1f}';
+/// Separates commits in the template output.
+const RECORD_SEPARATOR: char = '\u{1e}';
+
+/// The revset of every bookmarked commit. `remotenames()` needs Sapling's remotenames support.
+pub fn revset(with_remote: bool) -> &'static str {
+    if with_remote {
+        "bookmark() + remotenames()"
+    } else {
+        "bookmark()"
+    }
+}
+
+/// The template passed to `sl log -T` on [`revset`].
+pub fn template(with_remote: bool) -> String {
+    let mut fields = vec!["{node}", "{bookmarks}"];
+    if with_remote {
+        fields.push("{remotenames}");
+    }
+    let mut template = fields.join(&FIELD_SEPARATOR.to_string());
+    template.push(RECORD_SEPARATOR);
+    template
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    pub name: String,
+    pub node: String,
+    /// Remote bookmarks such as `remote/main` can't be moved or deleted from here.
+    pub remote: bool,
+}
+impl Bookmark {
+    /// Parse the output of [`template`], sorted with local bookmarks first, then by name.
+    pub fn parse_list(output: &str) -> Vec<Self> {
+        let mut bookmarks: Vec<Self> = output
+            .split(RECORD_SEPARATOR)
+            .flat_map(|record| {
+                let mut fields = record.trim_start_matches('\n').split(FIELD_SEPARATOR);
+                let node = fields.next().unwrap_or_default().to_string();
+                let local = fields.next().unwrap_or_default();
+                let remote = fields.next().unwrap_or_default();
+                let names = local
+                    .split_whitespace()
+                    .map(|name| (name, false))
+                    .chain(remote.split_whitespace().map(|name| (name, true)));
+                names
+                    .map(|(name, remote)| Self {
+                        name: name.to_string(),
+                        node: node.clone(),
+                        remote,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .filter(|bookmark| !bookmark.node.is_empty())
+            .collect();
+        bookmarks.sort_by(|a, b| (a.remote, &a.name).cmp(&(b.remote, &b.name)));
+        bookmarks
+    }
+}
+
+/// Check a new bookmark name the way Sapling would, to fail before running anything.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("bookmark names can't be empty".to_string());
+    }
+    if name.chars().any(|c| c.is_whitespace() || c == ':') {
+        return Err(format!(
+            "invalid bookmark name '{}': no spaces or ':' allowed",
+            name
+        ));
+    }
+    if name.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!(
+            "invalid bookmark name '{}': it can't be only digits",
+            name
+        ));
+    }
+    if [".", "null", "tip"].contains(&name) {
+        return Err(format!("'{}' is a reserved name", name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        let output = "ba27d4d13\u{1f}\u{1f}remote/main\u{1e}\n\
+                      c3bd9e5fa\u{1f}feature wip\u{1f}\u{1e}\n";
+        let bookmarks = Bookmark::parse_list(output);
+        let names: Vec<(&str, &str, bool)> = bookmarks
+            .iter()
+            .map(|b| (b.name.as_str(), b.node.as_str(), b.remote))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("feature", "c3bd9e5fa", false),
+                ("wip", "c3bd9e5fa", false),
+                ("remote/main", "ba27d4d13", true),
+            ]
+        );
+        assert!(Bookmark::parse_list("").is_empty());
+        // without remote names
+        assert_eq!(Bookmark::parse_list("abc\u{1f}main\u{1e}").len(), 1);
+    }
+
+    #[test]
+    fn test_template() {
+        assert!(template(true).contains("{remotenames}"));
+        assert!(!template(false).contains("{remotenames}"));
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("feature/x-1").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("two words").is_err());
+        assert!(validate_name("a:b").is_err());
+        assert!(validate_name("123").is_err());
+        assert!(validate_name("tip").is_err());
+    }
+}
@@ -0,0 +1,802 @@
+//! User configuration, read from a TOML file.
+//!
+//! The file is looked up at `$XDG_CONFIG_HOME/sl-up/config.toml` (falling back to `~/.config/sl-up/config.toml`),
+//! unless another path is given on the command line. Every setting is optional:
+//!
+//! ```toml
+//! [keymap]
+//! preset = "vim"  # "default", "vim" or "emacs"
+//! move_top = ["g g", "home"]
+//! select = ["enter"]
+//! quit = ["q", "esc", "ctrl-c"]
+//!
+//! [colors]
+//! selection = "magenta"
+//! selection_style = "text"  # "text", "reverse" or "background"
+//! mark = "yellow"
+//! search = "cyan"
+//!
+//! [smartlog]
+//! command = "ssl"
+//! revset = "draft()"
+//!
+//! [behavior]
+//! default_action = "goto"
+//! confirm = false
+//! mouse = true
+//! height = "40%"  # or a number of rows, to draw below the shell's output instead of full screen
+//! ```
+//!
+//! Settings can also come from the `[sl-up]` section of Sapling's own config (e.g. a repo's `.sl/config`),
+//! which takes precedence over the file above so teams can share per-repo defaults:
+//!
+//! ```ini
+//! [sl-up]
+//! revset = draft() + .
+//! default-action = goto
+//! confirm = true
+//! keymap.move-up = up, k
+//! color.selection = blue
+//! ```
+//!
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::keymap::{Action, KeySequence, Keymap, KeymapPreset};
+
+/// The configuration file as written by the user, before validation.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawConfig {
+    #[serde(default)]
+    pub keymap: RawKeymap,
+    #[serde(default)]
+    pub colors: RawColors,
+    #[serde(default)]
+    pub smartlog: RawSmartlog,
+    #[serde(default)]
+    pub behavior: RawBehavior,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RawKeymap {
+    /// The built-in keymap the bindings below are added to.
+    pub preset: Option<String>,
+    /// Keys for each action, replacing the preset's keys for that action.
+    #[serde(flatten)]
+    pub bindings: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawColors {
+    pub selection: Option<String>,
+    pub selection_style: Option<String>,
+    pub mark: Option<String>,
+    pub search: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawSmartlog {
+    pub command: Option<String>,
+    pub revset: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawBehavior {
+    pub default_action: Option<String>,
+    pub confirm: Option<bool>,
+    pub mouse: Option<bool>,
+    pub height: Option<String>,
+}
+
+/// What happens when a commit is selected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultAction {
+    /// Check out the commit with `sl goto`.
+    Goto,
+    /// Print the commit hash to stdout, for use in scripts.
+    Print,
+}
+impl DefaultAction {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "goto" => Some(DefaultAction::Goto),
+            "print" => Some(DefaultAction::Print),
+            _ => None,
+        }
+    }
+}
+
+/// How the selected commit is highlighted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelectionStyle {
+    /// Color the commit's text with the selection color, like Sapling does for the working copy.
+    #[default]
+    Text,
+    /// Draw the commit's rows in reverse video.
+    Reverse,
+    /// Fill the commit's rows with the selection color as background.
+    Background,
+}
+impl SelectionStyle {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "text" => Some(SelectionStyle::Text),
+            "reverse" => Some(SelectionStyle::Reverse),
+            "background" => Some(SelectionStyle::Background),
+            _ => None,
+        }
+    }
+}
+
+/// How much of the terminal the UI takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Height {
+    /// The whole screen, on the terminal's alternate screen.
+    #[default]
+    Full,
+    /// A number of rows below the shell's output.
+    Rows(u16),
+    /// A percentage of the terminal's rows below the shell's output.
+    Percent(u16),
+}
+impl Height {
+    /// The fewest rows the UI is drawn in, whatever the height asked for.
+    const MIN_ROWS: u16 = 5;
+
+    /// The number of rows taken on a terminal of `terminal_rows`.
+    ///
+    /// ```
+    /// # use sl_up::config::Height;
+    /// assert_eq!(Height::Full.rows(40), 40);
+    /// assert_eq!(Height::Rows(15).rows(40), 15);
+    /// assert_eq!(Height::Rows(50).rows(40), 40);
+    /// assert_eq!(Height::Percent(40).rows(40), 16);
+    /// assert_eq!(Height::Percent(1).rows(40), 5);
+    /// ```
+    pub fn rows(&self, terminal_rows: u16) -> u16 {
+        let rows = match self {
+            Height::Full => return terminal_rows,
+            Height::Rows(rows) => *rows,
+            Height::Percent(percent) => (terminal_rows as u32 * *percent as u32 / 100) as u16,
+        };
+        rows.max(Self::MIN_ROWS).min(terminal_rows)
+    }
+}
+impl std::str::FromStr for Height {
+    type Err = String;
+
+    /// Parse a number of rows (e.g. `15`) or a percentage of the terminal (e.g. `40%`), with
+    /// `100%` for the whole screen.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            format!(
+                "invalid height '{}' (expected a number of rows like '15' or a percentage like '40%')",
+                text
+            )
+        };
+        match text.trim().strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<u16>() {
+                Ok(100) => Ok(Height::Full),
+                Ok(percent @ 1..=99) => Ok(Height::Percent(percent)),
+                _ => Err(error()),
+            },
+            None => match text.trim().parse::<u16>() {
+                Ok(rows @ 1..) => Ok(Height::Rows(rows)),
+                _ => Err(error()),
+            },
+        }
+    }
+}
+
+/// ANSI escape sequences used to highlight commits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    pub selection: String,
+    pub selection_style: SelectionStyle,
+    pub mark: String,
+    pub search: String,
+}
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            selection: "\u{1b}[0;35m".to_string(),
+            selection_style: SelectionStyle::default(),
+            mark: "\u{1b}[0;33m".to_string(),
+            search: "\u{1b}[0;36m".to_string(),
+        }
+    }
+}
+
+/// How the smartlog is produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmartlogSettings {
+    /// The `sl` subcommand (and its arguments) printing the smartlog.
+    pub command: Vec<String>,
+    /// An optional revset passed to the command with `--rev`.
+    pub revset: Option<String>,
+}
+impl Default for SmartlogSettings {
+    fn default() -> Self {
+        Self {
+            command: vec!["ssl".to_string()],
+            revset: None,
+        }
+    }
+}
+
+/// The validated configuration.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub keymap: Keymap,
+    pub colors: Colors,
+    pub smartlog: SmartlogSettings,
+    pub default_action: DefaultAction,
+    /// Ask for confirmation before acting on the selected commit.
+    pub confirm: bool,
+    /// Capture the mouse to click on commits and scroll with the wheel.
+    pub mouse: bool,
+    pub height: Height,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            keymap: Keymap::default(),
+            colors: Colors::default(),
+            smartlog: SmartlogSettings::default(),
+            default_action: DefaultAction::Goto,
+            confirm: false,
+            mouse: true,
+            height: Height::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration from `path` if given, or from the default location if it exists.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+        let text = fs::read_to_string(&path).map_err(|err| ConfigError::Io(path.clone(), err))?;
+        Self::from_toml(&text).map_err(|err| err.with_source(path.display().to_string()))
+    }
+
+    /// The default location of the config file, following the XDG base directory spec.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("sl-up").join("config.toml"))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        let raw: RawConfig = toml::from_str(text)
+            .map_err(|err| ConfigError::Parse("config".to_string(), err.to_string()))?;
+        Self::from_raw(raw)
+    }
+
+    pub fn from_raw(raw: RawConfig) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        config
+            .apply(raw)
+            .map_err(|errors| ConfigError::Invalid("config".to_string(), errors))?;
+        Ok(config)
+    }
+
+    /// Override this configuration with the `[sl-up]` section of Sapling's own config,
+    /// given as `(key, value)` pairs such as `("keymap.quit", "q, esc")`.
+    pub fn apply_sapling_config(
+        &mut self,
+        entries: &[(String, String)],
+    ) -> Result<(), ConfigError> {
+        let source = "Sapling config [sl-up]".to_string();
+        let raw = RawConfig::from_sapling_config(entries)
+            .map_err(|errors| ConfigError::Invalid(source.clone(), errors))?;
+        self.apply(raw)
+            .map_err(|errors| ConfigError::Invalid(source, errors))
+    }
+
+    /// Override the settings present in `raw`, returning every validation error found.
+    fn apply(&mut self, raw: RawConfig) -> Result<(), Vec<String>> {
+        let config = self;
+        let mut errors = Vec::new();
+
+        if let Some(preset) = raw.keymap.preset {
+            match KeymapPreset::from_name(&preset) {
+                Some(preset) => config.keymap = Keymap::preset(preset),
+                None => errors.push(format!(
+                    "keymap.preset: unknown preset '{}' (expected 'default', 'vim' or 'emacs')",
+                    preset
+                )),
+            }
+        }
+
+        // sort for deterministic error messages
+        let mut keymap: Vec<(String, Vec<String>)> = raw.keymap.bindings.into_iter().collect();
+        keymap.sort();
+        let keymap: Vec<(String, Action, Vec<String>)> = keymap
+            .into_iter()
+            .filter_map(|(name, keys)| match Action::from_name(&name) {
+                Some(action) => Some((name, action, keys)),
+                None => {
+                    errors.push(format!("keymap: unknown action '{}'", name));
+                    None
+                }
+            })
+            .collect();
+        // the configured keys replace an action's keys from earlier layers, but not other
+        // actions' keys: a key taken from another action is a conflict
+        for (_, action, _) in &keymap {
+            config.keymap.unbind_action(*action);
+        }
+        for (name, action, keys) in keymap {
+            for key in keys {
+                match KeySequence::parse(&key) {
+                    Ok(binding) => {
+                        if let Some(other) = config.keymap.bind(binding, action) {
+                            if other != action {
+                                errors.push(format!(
+                                    "keymap: key '{}' is bound to both '{}' and '{}'",
+                                    key, other, action
+                                ));
+                            }
+                        }
+                    }
+                    Err(err) => errors.push(format!("keymap.{}: {}", name, err)),
+                }
+            }
+        }
+
+        for (name, value, target) in [
+            (
+                "selection",
+                raw.colors.selection,
+                &mut config.colors.selection,
+            ),
+            ("mark", raw.colors.mark, &mut config.colors.mark),
+            ("search", raw.colors.search, &mut config.colors.search),
+        ] {
+            if let Some(value) = value {
+                match parse_color(&value) {
+                    Some(escape) => *target = escape,
+                    None => errors.push(format!("colors.{}: unknown color '{}'", name, value)),
+                }
+            }
+        }
+
+        if let Some(style) = raw.colors.selection_style {
+            match SelectionStyle::parse(&style) {
+                Some(style) => config.colors.selection_style = style,
+                None => errors.push(format!(
+                    "colors.selection_style: unknown style '{}' (expected 'text', 'reverse' or 'background')",
+                    style
+                )),
+            }
+        }
+
+        if let Some(command) = raw.smartlog.command {
+            let command: Vec<String> = command.split_whitespace().map(String::from).collect();
+            if command.is_empty() {
+                errors.push("smartlog.command: must not be empty".to_string());
+            } else {
+                config.smartlog.command = command;
+            }
+        }
+        if let Some(revset) = raw.smartlog.revset {
+            // an empty revset clears one set by a previous layer
+            config.smartlog.revset = Some(revset).filter(|revset| !revset.trim().is_empty());
+        }
+
+        if let Some(default_action) = raw.behavior.default_action {
+            match DefaultAction::parse(&default_action) {
+                Some(default_action) => config.default_action = default_action,
+                None => errors.push(format!(
+                    "behavior.default_action: unknown action '{}' (expected 'goto' or 'print')",
+                    default_action
+                )),
+            }
+        }
+        if let Some(confirm) = raw.behavior.confirm {
+            config.confirm = confirm;
+        }
+        if let Some(mouse) = raw.behavior.mouse {
+            config.mouse = mouse;
+        }
+        if let Some(height) = raw.behavior.height {
+            match height.parse() {
+                Ok(height) => config.height = height,
+                Err(err) => errors.push(format!("behavior.height: {}", err)),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+impl RawConfig {
+    /// Build a raw config from Sapling config entries, where keymap values are comma separated.
+    fn from_sapling_config(entries: &[(String, String)]) -> Result<Self, Vec<String>> {
+        let mut raw = Self::default();
+        let mut errors = Vec::new();
+        for (key, value) in entries {
+            let name = key.replace('-', "_");
+            let value = value.trim().to_string();
+            match name.split_once('.') {
+                Some(("keymap", "preset")) => raw.keymap.preset = Some(value),
+                Some(("keymap", action)) => {
+                    let keys = value
+                        .split(',')
+                        .map(|key| key.trim().to_string())
+                        .filter(|key| !key.is_empty())
+                        .collect();
+                    raw.keymap.bindings.insert(action.to_string(), keys);
+                }
+                Some(("colors" | "color", "selection")) => raw.colors.selection = Some(value),
+                Some(("colors" | "color", "selection_style")) => {
+                    raw.colors.selection_style = Some(value)
+                }
+                Some(("colors" | "color", "mark")) => raw.colors.mark = Some(value),
+                Some(("colors" | "color", "search")) => raw.colors.search = Some(value),
+                _ => match name.as_str() {
+                    "command" => raw.smartlog.command = Some(value),
+                    "revset" => raw.smartlog.revset = Some(value),
+                    "default_action" => raw.behavior.default_action = Some(value),
+                    "height" => raw.behavior.height = Some(value),
+                    "confirm" | "mouse" => match parse_sapling_bool(&value) {
+                        Some(enabled) if name == "confirm" => raw.behavior.confirm = Some(enabled),
+                        Some(enabled) => raw.behavior.mouse = Some(enabled),
+                        None => errors.push(format!("{}: '{}' is not a boolean", key, value)),
+                    },
+                    _ => errors.push(format!("unknown setting '{}'", key)),
+                },
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(raw)
+    }
+}
+
+/// Parse a boolean the way Sapling does.
+fn parse_sapling_bool(text: &str) -> Option<bool> {
+    match text.to_lowercase().as_str() {
+        "1" | "yes" | "true" | "on" | "always" => Some(true),
+        "0" | "no" | "false" | "off" | "never" => Some(false),
+        _ => None,
+    }
+}
+
+/// Convert a color name (e.g. `"magenta"`, `"bright-red"`), a 24-bit `"#rrggbb"` value
+/// or raw SGR parameters (e.g. `"0;35"`) into an ANSI escape sequence.
+/// ```
+///  # use sl_up::config::parse_color;
+///  assert_eq!(parse_color("magenta").unwrap(), "\u{1b}[0;35m");
+///  assert_eq!(parse_color("#ff0000").unwrap(), "\u{1b}[0;38;2;255;0;0m");
+///  assert_eq!(parse_color("1;4;32").unwrap(), "\u{1b}[1;4;32m");
+/// ```
+pub fn parse_color(text: &str) -> Option<String> {
+    const NAMES: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    let text = text.trim().to_lowercase();
+
+    if let Some(hex) = text.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(format!(
+            "\u{1b}[0;38;2;{};{};{}m",
+            channel(0)?,
+            channel(2)?,
+            channel(4)?
+        ));
+    }
+    if !text.is_empty() && text.chars().all(|c| c.is_ascii_digit() || c == ';') {
+        return Some(format!("\u{1b}[{}m", text));
+    }
+
+    let (base, name) = match text.strip_prefix("bright-") {
+        Some(name) => (90, name),
+        None => (30, text.as_str()),
+    };
+    let idx = NAMES.iter().position(|color| *color == name)?;
+    Some(format!("\u{1b}[0;{}m", base + idx))
+}
+
+/// An error found while loading the configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    /// The config couldn't be parsed. Holds where the config came from and the parser's message.
+    Parse(String, String),
+    /// Some settings have invalid values. Holds where the config came from and every problem found.
+    Invalid(String, Vec<String>),
+}
+impl ConfigError {
+    fn with_source(self, source: String) -> Self {
+        match self {
+            ConfigError::Parse(_, message) => ConfigError::Parse(source, message),
+            ConfigError::Invalid(_, errors) => ConfigError::Invalid(source, errors),
+            err => err,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "can't read {}: {}", path.display(), err),
+            ConfigError::Parse(source, message) => {
+                write!(f, "invalid config in {}: {}", source, message)
+            }
+            ConfigError::Invalid(source, errors) => {
+                writeln!(f, "invalid config in {}:", source)?;
+                for error in errors {
+                    writeln!(f, "  - {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::*;
+
+    #[test]
+    fn test_empty_config() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config.colors, Colors::default());
+        assert_eq!(config.smartlog, SmartlogSettings::default());
+        assert_eq!(config.default_action, DefaultAction::Goto);
+        assert!(!config.confirm);
+        assert!(config.mouse);
+        assert_eq!(config.height, Height::Full);
+    }
+
+    #[test]
+    fn test_full_config() {
+        let config = Config::from_toml(
+            r##"
+            [keymap]
+            move_up = ["k"]
+            move_down = ["j", "down"]
+
+            [colors]
+            selection = "bright-blue"
+            selection_style = "background"
+            mark = "#00ff00"
+
+            [smartlog]
+            command = "smartlog -T {ssl}"
+            revset = "draft()"
+
+            [behavior]
+            default_action = "print"
+            confirm = true
+            mouse = false
+            height = "40%"
+            "##,
+        )
+        .unwrap();
+
+        let up = KeyEvent::new(KeyCode::Up, KeyModifiers::NONE);
+        let k = KeyEvent::new(KeyCode::Char('k'), KeyModifiers::NONE);
+        assert_eq!(config.keymap.action_for(&up), None);
+        assert_eq!(config.keymap.action_for(&k), Some(Action::MoveUp));
+        assert_eq!(config.keymap.keys_for(Action::MoveDown).len(), 2);
+        // untouched actions keep their defaults
+        assert_eq!(config.keymap.keys_for(Action::Quit).len(), 3);
+
+        assert_eq!(config.colors.selection, "\u{1b}[0;94m");
+        assert_eq!(config.colors.selection_style, SelectionStyle::Background);
+        assert_eq!(config.colors.mark, "\u{1b}[0;38;2;0;255;0m");
+        assert_eq!(config.colors.search, Colors::default().search);
+        assert_eq!(config.smartlog.command, vec!["smartlog", "-T", "{ssl}"]);
+        assert_eq!(config.smartlog.revset.as_deref(), Some("draft()"));
+        assert_eq!(config.default_action, DefaultAction::Print);
+        assert!(config.confirm);
+        assert!(!config.mouse);
+        assert_eq!(config.height, Height::Percent(40));
+    }
+
+    #[test]
+    fn test_keymap_preset() {
+        let config = Config::from_toml(
+            r#"
+            [keymap]
+            preset = "vim"
+            move_top = ["home"]
+            "#,
+        )
+        .unwrap();
+        let j = KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE);
+        assert_eq!(config.keymap.action_for(&j), Some(Action::MoveDown));
+        assert_eq!(
+            config.keymap.keys_for(Action::MoveTop),
+            vec![KeySequence::parse("home").unwrap()]
+        );
+
+        let err = Config::from_toml("[keymap]\npreset = \"nano\"").unwrap_err();
+        assert!(err.to_string().contains("unknown preset 'nano'"));
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let err = Config::from_toml(
+            r#"
+            [keymap]
+            move_up = ["hyper-k"]
+            jump = ["j"]
+            quit = ["x"]
+            select = ["x"]
+
+            [colors]
+            selection = "purple-ish"
+            selection_style = "blink"
+
+            [behavior]
+            default_action = "explode"
+            height = "tall"
+            "#,
+        )
+        .unwrap_err();
+        let ConfigError::Invalid(_, errors) = &err else {
+            panic!("Expected ConfigError::Invalid, got {:?}", err);
+        };
+        assert_eq!(errors.len(), 7);
+        let message = err.to_string();
+        assert!(message.contains("unknown action 'jump'"));
+        assert!(message.contains("unknown modifier 'hyper'"));
+        assert!(message.contains("key 'x' is bound to both"));
+        assert!(message.contains("unknown color 'purple-ish'"));
+        assert!(message.contains("unknown style 'blink'"));
+        assert!(message.contains("unknown action 'explode'"));
+        assert!(message.contains("invalid height 'tall'"));
+    }
+
+    #[test]
+    fn test_key_conflicts_across_layers() {
+        // a default key of another action
+        let err = Config::from_toml("[keymap]\nselect = [\"q\"]").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("key 'q' is bound to both 'quit' and 'select'"));
+        // unless that action is rebound too
+        let config = Config::from_toml("[keymap]\nselect = [\"q\"]\nquit = [\"esc\"]").unwrap();
+        let q = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE);
+        assert_eq!(config.keymap.action_for(&q), Some(Action::Select));
+
+        // a key of the user config, taken in Sapling's config
+        let mut config = Config::from_toml("[keymap]\nmove_up = [\"k\"]").unwrap();
+        let entries = vec![("keymap.select".to_string(), "k".to_string())];
+        let err = config.apply_sapling_config(&entries).unwrap_err();
+        assert!(err.to_string().contains("key 'k' is bound to both"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            Config::from_toml("[colors]\nbackground = \"red\""),
+            Err(ConfigError::Parse(..))
+        ));
+        assert!(matches!(
+            Config::from_toml("[behavior]\nconfirm = \"yes\""),
+            Err(ConfigError::Parse(..))
+        ));
+    }
+
+    #[test]
+    fn test_sapling_config() {
+        let mut config = Config::from_toml(
+            r#"
+            [keymap]
+            move_up = ["k"]
+
+            [smartlog]
+            revset = "draft()"
+            "#,
+        )
+        .unwrap();
+        let entries: Vec<(String, String)> = [
+            ("revset", "smartlog()"),
+            ("default-action", "print"),
+            ("height", "15"),
+            ("confirm", "yes"),
+            ("keymap.move-up", "up, ctrl-p"),
+            ("color.selection", "blue"),
+            ("color.selection-style", "reverse"),
+            ("color.search", "green"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        config.apply_sapling_config(&entries).unwrap();
+
+        assert_eq!(config.smartlog.revset.as_deref(), Some("smartlog()"));
+        assert_eq!(config.default_action, DefaultAction::Print);
+        assert!(config.confirm);
+        assert_eq!(config.height, Height::Rows(15));
+        assert_eq!(
+            config.keymap.keys_for(Action::MoveUp),
+            vec![
+                KeySequence::parse("up").unwrap(),
+                KeySequence::parse("ctrl-p").unwrap()
+            ]
+        );
+        assert_eq!(config.colors.selection, "\u{1b}[0;34m");
+        assert_eq!(config.colors.selection_style, SelectionStyle::Reverse);
+        assert_eq!(config.colors.search, "\u{1b}[0;32m");
+    }
+
+    #[test]
+    fn test_sapling_config_errors() {
+        let mut config = Config::default();
+        let entries = vec![
+            ("confirm".to_string(), "maybe".to_string()),
+            ("colour".to_string(), "red".to_string()),
+        ];
+        let err = config.apply_sapling_config(&entries).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("Sapling config [sl-up]"));
+        assert!(message.contains("confirm: 'maybe' is not a boolean"));
+        assert!(message.contains("unknown setting 'colour'"));
+
+        let entries = vec![("keymap.quit".to_string(), "hyper-q".to_string())];
+        let err = config.apply_sapling_config(&entries).unwrap_err();
+        assert!(err.to_string().contains("unknown modifier 'hyper'"));
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let err = Config::load(Some(Path::new("/nonexistent/sl-up.toml"))).unwrap_err();
+        assert!(matches!(err, ConfigError::Io(..)));
+        assert!(err.to_string().contains("/nonexistent/sl-up.toml"));
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("Red").unwrap(), "\u{1b}[0;31m");
+        assert_eq!(parse_color("bright-white").unwrap(), "\u{1b}[0;97m");
+        assert!(parse_color("#12345").is_none());
+        assert!(parse_color("#gg0000").is_none());
+        assert!(parse_color("").is_none());
+        assert!(parse_color("rainbow").is_none());
+    }
+
+    #[test]
+    fn test_parse_height() {
+        assert_eq!("15".parse(), Ok(Height::Rows(15)));
+        assert_eq!(" 40% ".parse(), Ok(Height::Percent(40)));
+        assert_eq!("100%".parse(), Ok(Height::Full));
+        assert!("0".parse::<Height>().is_err());
+        assert!("0%".parse::<Height>().is_err());
+        assert!("150%".parse::<Height>().is_err());
+        assert!("-3".parse::<Height>().is_err());
+        assert!("half".parse::<Height>().is_err());
+    }
+}
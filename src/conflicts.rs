@@ -0,0 +1,239 @@
+//! State of the conflict resolution panel, shown when a goto, rebase or graft stops with conflicts.
+//!
+use std::path::Path;
+
+/// An operation Sapling was in the middle of when it stopped for conflicts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnfinishedOperation {
+    Rebase,
+    Graft,
+    Unshelve,
+    Histedit,
+    /// A `goto --merge`.
+    Update,
+    Merge,
+}
+impl UnfinishedOperation {
+    /// State files Sapling leaves in its `.sl` directory, most specific first.
+    const STATE_FILES: [(&'static str, UnfinishedOperation); 7] = [
+        ("rebasestate", UnfinishedOperation::Rebase),
+        ("graftstate", UnfinishedOperation::Graft),
+        ("shelvedstate", UnfinishedOperation::Unshelve),
+        ("histedit-state", UnfinishedOperation::Histedit),
+        ("updatemergestate", UnfinishedOperation::Update),
+        ("updatestate", UnfinishedOperation::Update),
+        ("merge/state2", UnfinishedOperation::Merge),
+    ];
+
+    /// Detect the unfinished operation from the state files in `dot_dir` (e.g. `<repo>/.sl`).
+    pub fn detect(dot_dir: &Path) -> Option<Self> {
+        Self::STATE_FILES
+            .iter()
+            .find(|(file, _)| dot_dir.join(file).exists())
+            .map(|(_, operation)| *operation)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnfinishedOperation::Rebase => "rebase",
+            UnfinishedOperation::Graft => "graft",
+            UnfinishedOperation::Unshelve => "unshelve",
+            UnfinishedOperation::Histedit => "histedit",
+            UnfinishedOperation::Update => "goto",
+            UnfinishedOperation::Merge => "merge",
+        }
+    }
+
+    /// Whether `sl continue` has anything to do once the conflicts are resolved. A bare merge
+    /// state, with no operation to finish, is done as soon as every file is resolved.
+    pub fn can_continue(&self) -> bool {
+        !matches!(self, UnfinishedOperation::Merge)
+    }
+
+    /// The `sl` arguments that abort this operation, restoring the state from before it started.
+    pub fn abort_args(&self) -> Vec<&'static str> {
+        match self {
+            UnfinishedOperation::Rebase => vec!["rebase", "--abort"],
+            UnfinishedOperation::Graft => vec!["graft", "--abort"],
+            UnfinishedOperation::Unshelve => vec!["unshelve", "--abort"],
+            UnfinishedOperation::Histedit => vec!["histedit", "--abort"],
+            UnfinishedOperation::Update | UnfinishedOperation::Merge => {
+                vec!["goto", "--clean", "."]
+            }
+        }
+    }
+}
+
+/// A file with merge conflicts, as listed by `sl resolve --list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictedFile {
+    pub path: String,
+    pub resolved: bool,
+}
+impl ConflictedFile {
+    /// Parse the output of `sl resolve --list`, e.g. `U src/main.rs` or `R src/lib.rs`.
+    pub fn parse_list(output: &str) -> Vec<Self> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let (status, path) = line.split_once(' ')?;
+                let resolved = match status {
+                    "R" => true,
+                    "U" => false,
+                    _ => return None,
+                };
+                Some(Self {
+                    path: path.to_string(),
+                    resolved,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The files being resolved and the one under the cursor.
+#[derive(Debug)]
+pub struct ConflictPanel {
+    pub operation: Option<UnfinishedOperation>,
+    pub files: Vec<ConflictedFile>,
+    pub cursor: usize,
+}
+impl ConflictPanel {
+    pub fn new(operation: Option<UnfinishedOperation>, files: Vec<ConflictedFile>) -> Self {
+        Self {
+            operation,
+            files,
+            cursor: 0,
+        }
+    }
+
+    /// Replace the list of files, keeping the cursor on the same file if it's still listed.
+    pub fn update_files(&mut self, files: Vec<ConflictedFile>) {
+        let current = self.selected().map(|file| file.path.clone());
+        self.files = files;
+        self.cursor = current
+            .and_then(|path| self.files.iter().position(|file| file.path == path))
+            .unwrap_or(0);
+    }
+
+    pub fn selected(&self) -> Option<&ConflictedFile> {
+        self.files.get(self.cursor)
+    }
+
+    pub fn move_up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        if self.cursor + 1 < self.files.len() {
+            self.cursor += 1;
+        }
+    }
+
+    pub fn unresolved_count(&self) -> usize {
+        self.files.iter().filter(|file| !file.resolved).count()
+    }
+
+    /// Whether there is anything left to do: files to resolve or an operation to continue.
+    pub fn needs_resolution(&self) -> bool {
+        self.unresolved_count() > 0 || self.operation.is_some_and(|op| op.can_continue())
+    }
+
+    pub fn title(&self) -> String {
+        let operation = self
+            .operation
+            .map(|operation| format!("Unfinished {}", operation.name()))
+            .unwrap_or_else(|| "Merge conflicts".to_string());
+        format!(
+            "{}: {} of {} file(s) unresolved",
+            operation,
+            self.unresolved_count(),
+            self.files.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        let files = ConflictedFile::parse_list("U src/main.rs\nR src/with space.rs\n\n? x\n");
+        assert_eq!(
+            files,
+            vec![
+                ConflictedFile {
+                    path: "src/main.rs".to_string(),
+                    resolved: false
+                },
+                ConflictedFile {
+                    path: "src/with space.rs".to_string(),
+                    resolved: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detect() {
+        let dot_dir = std::env::temp_dir().join(format!("sl-up-conflicts-{}", std::process::id()));
+        fs::create_dir_all(dot_dir.join("merge")).unwrap();
+        assert_eq!(UnfinishedOperation::detect(&dot_dir), None);
+
+        fs::write(dot_dir.join("merge/state2"), "").unwrap();
+        assert_eq!(
+            UnfinishedOperation::detect(&dot_dir),
+            Some(UnfinishedOperation::Merge)
+        );
+        fs::write(dot_dir.join("rebasestate"), "").unwrap();
+        assert_eq!(
+            UnfinishedOperation::detect(&dot_dir),
+            Some(UnfinishedOperation::Rebase)
+        );
+        fs::remove_dir_all(&dot_dir).unwrap();
+    }
+
+    #[test]
+    fn test_abort_args() {
+        assert_eq!(
+            UnfinishedOperation::Rebase.abort_args(),
+            vec!["rebase", "--abort"]
+        );
+        assert_eq!(
+            UnfinishedOperation::Update.abort_args(),
+            vec!["goto", "--clean", "."]
+        );
+    }
+
+    #[test]
+    fn test_panel() {
+        let files = ConflictedFile::parse_list("U a\nU b\nR c\n");
+        let mut panel = ConflictPanel::new(Some(UnfinishedOperation::Rebase), files);
+        assert_eq!(
+            panel.title(),
+            "Unfinished rebase: 2 of 3 file(s) unresolved"
+        );
+        panel.move_up();
+        assert_eq!(panel.cursor, 0);
+        panel.move_down();
+        panel.move_down();
+        panel.move_down();
+        assert_eq!(panel.selected().unwrap().path, "c");
+
+        panel.move_up();
+        panel.update_files(ConflictedFile::parse_list("R a\nR b\nR c\n"));
+        assert_eq!(panel.selected().unwrap().path, "b");
+        assert_eq!(panel.unresolved_count(), 0);
+        assert!(panel.needs_resolution());
+        panel.operation = Some(UnfinishedOperation::Merge);
+        assert!(!panel.needs_resolution());
+        panel.update_files(ConflictedFile::parse_list("R a\n"));
+        assert_eq!(panel.cursor, 0);
+
+        let panel = ConflictPanel::new(None, Vec::new());
+        assert_eq!(panel.title(), "Merge conflicts: 0 of 0 file(s) unresolved");
+    }
+}
@@ -0,0 +1,350 @@
+//! Full details of commits, shown in the detail panel for the commit under the cursor.
+//!
+//! The smartlog only shows a short hash and the first line of each commit's description,
+//! so the rest is fetched with `sl log -T <template>` and cached.
+//!
+use std::collections::HashMap;
+
+use crate::sapling_cmd::sl_log;
+
+/// Separates the fields of a commit in the template output.
+const FIELD_SEPARATOR: char = '\u{1f}';
+/// Separates commits in the template output.
+const RECORD_SEPARATOR: char = '\u{1e}';
+const FIELDS: [&str; 7] = [
+    "{node}",
+    "{author|email}",
+    "{date|isodate}",
+    "{date|age}",
+    "{phase}",
+    "{bookmarks}",
+    "{desc}",
+];
+/// The most commits fetched by a single `sl log` call, to keep its revset argument short.
+const BATCH_SIZE: usize = 500;
+/// Only available when Sapling's GitHub integration is enabled.
+const PULL_REQUEST_FIELD: &str = "{github_pull_request_url}";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitDetails {
+    pub node: String,
+    pub author_email: String,
+    pub date: String,
+    pub relative_date: String,
+    pub phase: String,
+    pub bookmarks: Vec<String>,
+    pub description: String,
+    pub pull_request_url: Option<String>,
+}
+impl CommitDetails {
+    /// Parse the output of [`template`] for a single commit.
+    fn parse(record: &str) -> Option<Self> {
+        let mut fields = record.split(FIELD_SEPARATOR);
+        let mut next = || fields.next().map(|field| field.to_string());
+        let details = Self {
+            node: next()?,
+            author_email: next()?,
+            date: next()?,
+            relative_date: next()?,
+            phase: next()?,
+            bookmarks: next()?.split_whitespace().map(String::from).collect(),
+            description: next()?,
+            pull_request_url: next().filter(|url| !url.is_empty()),
+        };
+        if details.node.is_empty() {
+            return None;
+        }
+        Some(details)
+    }
+
+    /// The lines of the detail panel, at most `height` lines of at most `width` characters.
+    pub fn panel_lines(&self, ci_signal: Option<&str>, width: usize, height: usize) -> Vec<String> {
+        let mut header = format!("{} ({})", self.node, self.phase);
+        if !self.bookmarks.is_empty() {
+            header.push_str(&format!("  bookmarks: {}", self.bookmarks.join(", ")));
+        }
+        let mut lines = vec![
+            header,
+            format!(
+                "{}  {} ({})",
+                self.author_email, self.date, self.relative_date
+            ),
+        ];
+        let mut review = Vec::new();
+        if let Some(url) = &self.pull_request_url {
+            review.push(format!("PR: {}", url));
+        }
+        if let Some(signal) = ci_signal {
+            review.push(format!("CI: {} {}", signal, describe_ci_signal(signal)));
+        }
+        if !review.is_empty() {
+            lines.push(review.join("  "));
+        }
+        lines.extend(self.description.lines().map(String::from));
+
+        lines
+            .into_iter()
+            .take(height)
+            .map(|line| line.chars().take(width).collect())
+            .collect()
+    }
+}
+
+/// Describe the CI signal Sapling draws next to a pull request in the smartlog.
+pub fn describe_ci_signal(signal: &str) -> &'static str {
+    match signal {
+        "✓" => "passing",
+        "✗" => "failing",
+        "‼" => "warning",
+        "⋯" => "pending",
+        _ => "unknown",
+    }
+}
+
+fn template(with_pull_request: bool) -> String {
+    let mut fields = FIELDS.to_vec();
+    if with_pull_request {
+        fields.push(PULL_REQUEST_FIELD);
+    }
+    let mut template = fields.join(&FIELD_SEPARATOR.to_string());
+    template.push(RECORD_SEPARATOR);
+    template
+}
+
+fn parse_records(output: &str) -> Vec<CommitDetails> {
+    output
+        .split(RECORD_SEPARATOR)
+        .filter_map(CommitDetails::parse)
+        .collect()
+}
+
+/// Commit details fetched so far, keyed by the (possibly short) hash they were requested with.
+///
+/// The UI fetches each commit on its own when first shown, so one unknown or hidden commit
+/// doesn't keep the others from being shown. Commits without details are cached too, so
+/// `sl log` runs once per commit rather than on every render.
+#[derive(Debug, Default)]
+pub struct DetailsCache {
+    /// `None` for commits `sl log` printed no details for.
+    details: HashMap<String, Option<CommitDetails>>,
+    /// Why `sl log` failed for a commit.
+    errors: HashMap<String, String>,
+    /// Whether the GitHub template keywords turned out to be unknown, to stop trying them.
+    without_pull_requests: bool,
+}
+impl DetailsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&CommitDetails> {
+        self.details.get(hash).and_then(Option::as_ref)
+    }
+
+    /// The details of `hash`, fetched with `sl log` unless it was looked up already.
+    /// Returns `None` if `sl log` printed no details for it, or why it failed.
+    pub fn fetch(&mut self, hash: &str) -> Result<Option<&CommitDetails>, &str> {
+        if !self.is_cached(hash) {
+            match self.log(hash) {
+                Ok(output) => self.insert(&[hash], &output),
+                Err(err) => {
+                    self.errors
+                        .insert(hash.to_string(), err.to_string().trim().to_string());
+                }
+            }
+        }
+        match self.errors.get(hash) {
+            Some(err) => Err(err),
+            None => Ok(self.get(hash)),
+        }
+    }
+
+    /// Fetch the details of every hash not looked up already, with as few `sl log` calls as
+    /// possible: one for up to 500 commits, unless one of them makes `sl log` fail, e.g. a
+    /// hidden commit. The batch is then split until the commits it fails on are fetched alone.
+    pub fn fetch_all(&mut self, hashes: &[&str]) {
+        let missing: Vec<&str> = hashes
+            .iter()
+            .copied()
+            .filter(|hash| !self.is_cached(hash))
+            .collect();
+        for batch in missing.chunks(BATCH_SIZE) {
+            self.fetch_batch(batch);
+        }
+    }
+
+    fn fetch_batch(&mut self, hashes: &[&str]) {
+        match hashes {
+            [] => {}
+            [hash] => {
+                let _ = self.fetch(hash);
+            }
+            _ => match self.log(&hashes.join(" + ")) {
+                Ok(output) => self.insert(hashes, &output),
+                Err(_) => {
+                    let (first, second) = hashes.split_at(hashes.len() / 2);
+                    self.fetch_batch(first);
+                    self.fetch_batch(second);
+                }
+            },
+        }
+    }
+
+    fn is_cached(&self, hash: &str) -> bool {
+        self.details.contains_key(hash) || self.errors.contains_key(hash)
+    }
+
+    fn log(&mut self, revset: &str) -> Result<String, Box<dyn std::error::Error>> {
+        if !self.without_pull_requests {
+            match sl_log(revset, &template(true)) {
+                Ok(output) => return Ok(output),
+                // the GitHub template keywords are unknown without the extension
+                Err(err) => match sl_log(revset, &template(false)) {
+                    Ok(output) => {
+                        self.without_pull_requests = true;
+                        return Ok(output);
+                    }
+                    Err(_) => return Err(err),
+                },
+            }
+        }
+        sl_log(revset, &template(false))
+    }
+
+    /// Cache the details of `hashes` from the `output` of `sl log`, or their lack of details.
+    fn insert(&mut self, hashes: &[&str], output: &str) {
+        let mut records = parse_records(output);
+        for hash in hashes {
+            let details = records
+                .iter()
+                .position(|details| details.node.starts_with(hash))
+                .map(|idx| records.swap_remove(idx));
+            self.details.insert(hash.to_string(), details);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.details.clear();
+        self.errors.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(fields: &[&str]) -> String {
+        let mut record = fields.join(&FIELD_SEPARATOR.to_string());
+        record.push(RECORD_SEPARATOR);
+        record
+    }
+
+    #[test]
+    fn test_template() {
+        assert!(template(true).contains(PULL_REQUEST_FIELD));
+        assert!(!template(false).contains(PULL_REQUEST_FIELD));
+        assert!(template(false).ends_with(RECORD_SEPARATOR));
+    }
+
+    #[test]
+    fn test_insert() {
+        let output = record(&[
+            "1cee5d55e0123456789abcdef0123456789abcde",
+            "roy@example.com",
+            "2023-12-08 09:46 -0800",
+            "10 days ago",
+            "draft",
+            "feature fix-typo",
+            "[pr body update] update stack list\n\nSummary: longer text",
+            "https://github.com/facebook/sapling/pull/780",
+        ]) + &record(&[
+            "ba27d4d13fedcba9876543210fedcba987654321",
+            "other@example.com",
+            "2023-12-07 22:20 -0800",
+            "11 days ago",
+            "public",
+            "",
+            "landed commit",
+        ]);
+
+        let mut cache = DetailsCache::new();
+        cache.insert(&["1cee5d55e", "ba27d4d13", "0123abcde"], &output);
+
+        let details = cache.get("1cee5d55e").unwrap();
+        assert_eq!(details.author_email, "roy@example.com");
+        assert_eq!(details.phase, "draft");
+        assert_eq!(details.bookmarks, vec!["feature", "fix-typo"]);
+        assert!(details.description.ends_with("Summary: longer text"));
+        assert_eq!(
+            details.pull_request_url.as_deref(),
+            Some("https://github.com/facebook/sapling/pull/780")
+        );
+
+        let details = cache.get("ba27d4d13").unwrap();
+        assert_eq!(details.relative_date, "11 days ago");
+        assert!(details.bookmarks.is_empty());
+        assert_eq!(details.pull_request_url, None);
+        // not in the output, and not fetched again
+        assert!(cache.is_cached("0123abcde"));
+        assert!(cache.get("0123abcde").is_none());
+
+        cache.clear();
+        assert!(cache.get("1cee5d55e").is_none());
+    }
+
+    #[test]
+    fn test_cached_misses() {
+        let mut cache = DetailsCache::new();
+        // fetched already, without details or with an error: `sl log` isn't run again
+        cache.insert(&["0123abcde"], "");
+        assert_eq!(cache.fetch("0123abcde"), Ok(None));
+        cache
+            .errors
+            .insert("c3bd9e5fa".to_string(), "hidden revision".to_string());
+        assert_eq!(cache.fetch("c3bd9e5fa"), Err("hidden revision"));
+        assert!(cache.get("c3bd9e5fa").is_none());
+        cache.fetch_all(&["0123abcde", "c3bd9e5fa"]);
+    }
+
+    #[test]
+    fn test_panel_lines() {
+        let details = CommitDetails {
+            node: "1cee5d55e0123456789abcdef0123456789abcde".to_string(),
+            author_email: "roy@example.com".to_string(),
+            date: "2023-12-08 09:46 -0800".to_string(),
+            relative_date: "10 days ago".to_string(),
+            phase: "draft".to_string(),
+            bookmarks: vec!["feature".to_string()],
+            description: "title\n\nSummary: longer text".to_string(),
+            pull_request_url: Some("https://github.com/facebook/sapling/pull/780".to_string()),
+        };
+        let lines = details.panel_lines(Some("✗"), 200, 10);
+        assert_eq!(
+            lines,
+            vec![
+                "1cee5d55e0123456789abcdef0123456789abcde (draft)  bookmarks: feature",
+                "roy@example.com  2023-12-08 09:46 -0800 (10 days ago)",
+                "PR: https://github.com/facebook/sapling/pull/780  CI: ✗ failing",
+                "title",
+                "",
+                "Summary: longer text",
+            ]
+        );
+
+        let lines = details.panel_lines(None, 10, 2);
+        assert_eq!(lines, vec!["1cee5d55e0", "roy@exampl"]);
+    }
+
+    #[test]
+    fn test_parse_incomplete_record() {
+        assert!(CommitDetails::parse("").is_none());
+        assert!(CommitDetails::parse("abc\u{1f}roy@example.com").is_none());
+    }
+
+    #[test]
+    fn test_describe_ci_signal() {
+        assert_eq!(describe_ci_signal("✓"), "passing");
+        assert_eq!(describe_ci_signal("✗"), "failing");
+        assert_eq!(describe_ci_signal("?"), "unknown");
+    }
+}
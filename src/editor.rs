@@ -0,0 +1,105 @@
+//! Opening files in the user's editor.
+//!
+use std::{
+    ffi::OsStr,
+    fs,
+    io::{self, Write},
+    path::Path,
+    process::Command,
+};
+
+use tempfile::NamedTempFile;
+
+const DEFAULT_EDITOR: &str = "vi";
+
+/// The editor to use: `$VISUAL`, then `$EDITOR`, then `vi`.
+pub fn editor() -> String {
+    pick_editor(std::env::var("VISUAL").ok(), std::env::var("EDITOR").ok())
+}
+
+fn pick_editor(visual: Option<String>, editor: Option<String>) -> String {
+    [visual, editor]
+        .into_iter()
+        .flatten()
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string())
+}
+
+/// A command opening `path` in the editor. The editor goes through the shell,
+/// so it can include arguments such as `code --wait`.
+pub fn editor_command(path: impl AsRef<OsStr>) -> Command {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor()))
+        .arg("sh")
+        .arg(path);
+    command
+}
+
+/// A file in the temp directory for the user to edit, removed once dropped.
+///
+/// The file is created with a random name that must not exist yet, readable by the user only,
+/// so another user can't swap in a symlink to a file of ours or read the text.
+#[derive(Debug)]
+pub struct TempFile {
+    file: NamedTempFile,
+}
+impl TempFile {
+    /// Create `sl-up-<random>-<name>` holding `contents`.
+    pub fn new(name: &str, contents: &str) -> io::Result<Self> {
+        let mut file = tempfile::Builder::new()
+            .prefix("sl-up-")
+            .suffix(&format!("-{}", name))
+            .tempfile()?;
+        file.write_all(contents.as_bytes())?;
+        file.flush()?;
+        Ok(Self { file })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// The file's text once edited, which editors may have saved as a new file at its path.
+    pub fn read(&self) -> io::Result<String> {
+        fs::read_to_string(self.path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_editor() {
+        let some = |editor: &str| Some(editor.to_string());
+        assert_eq!(
+            pick_editor(some("code --wait"), some("nano")),
+            "code --wait"
+        );
+        assert_eq!(pick_editor(some(""), some("nano")), "nano");
+        assert_eq!(pick_editor(None, some("nano")), "nano");
+        assert_eq!(pick_editor(None, None), "vi");
+    }
+
+    #[test]
+    fn test_temp_file() {
+        let file = TempFile::new("test-message.txt", "title\n").unwrap();
+        let path = file.path().to_path_buf();
+        assert_eq!(file.read().unwrap(), "title\n");
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("sl-up-") && name.ends_with("-test-message.txt"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        // a second file of the same name doesn't clash with the first
+        let other = TempFile::new("test-message.txt", "other\n").unwrap();
+        assert_ne!(other.path(), path);
+        drop(file);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,226 @@
+//! Checking that a range of commits can be folded into one.
+//!
+//! The range is picked on the smartlog by its two ends, in any order. It has to be a straight
+//! line of draft commits: one end an ancestor of the other, without merges or public commits.
+//!
+/// Separates the fields of a commit in the template output.
+const FIELD_SEPARATOR: char = '\u{1f}';
+/// Separates commits in the template output.
+const RECORD_SEPARATOR: char = '\u{1e}';
+
+/// Every commit between the two ends, whichever is the ancestor. Empty if neither is.
+pub fn revset(first: &str, second: &str) -> String {
+    format!("({first}::{second}) + ({second}::{first})")
+}
+
+/// The template passed to `sl log -T` on [`revset`].
+pub fn template() -> String {
+    let fields = ["{node}", "{phase}", "{graphnode}", "{p1node}", "{p2node}"];
+    let mut template = fields.join(&FIELD_SEPARATOR.to_string());
+    template.push(RECORD_SEPARATOR);
+    template
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeCommit {
+    pub node: String,
+    pub phase: String,
+    /// Whether this is the working copy's parent.
+    pub working_copy: bool,
+    pub parents: Vec<String>,
+}
+impl RangeCommit {
+    /// Parse the output of [`template`].
+    pub fn parse_list(output: &str) -> Vec<Self> {
+        output
+            .split(RECORD_SEPARATOR)
+            .filter_map(|record| {
+                let mut fields = record.trim_start_matches('\n').split(FIELD_SEPARATOR);
+                let node = fields.next().filter(|node| !node.is_empty())?.to_string();
+                let phase = fields.next()?.to_string();
+                let working_copy = fields.next()? == "@";
+                let parents = fields
+                    // the null node stands for a missing parent
+                    .filter(|parent| !parent.is_empty() && parent.chars().any(|c| c != '0'))
+                    .map(String::from)
+                    .collect();
+                Some(Self {
+                    node,
+                    phase,
+                    working_copy,
+                    parents,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A validated range, from its oldest commit to its newest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoldRange {
+    pub commits: Vec<RangeCommit>,
+}
+impl FoldRange {
+    /// Check that `commits`, the output of [`revset`] for two ends, form a foldable stack.
+    pub fn validate(commits: Vec<RangeCommit>) -> Result<Self, String> {
+        if commits.is_empty() {
+            return Err(
+                "The two commits aren't in the same stack: neither is an ancestor of the other."
+                    .to_string(),
+            );
+        }
+        if commits.len() < 2 {
+            return Err("Select at least two commits to fold.".to_string());
+        }
+        if let Some(commit) = commits.iter().find(|commit| commit.phase != "draft") {
+            return Err(format!(
+                "{} is {}: only draft commits can be folded.",
+                short(&commit.node),
+                commit.phase
+            ));
+        }
+        if let Some(commit) = commits.iter().find(|commit| commit.parents.len() > 1) {
+            return Err(format!(
+                "{} is a merge: only a straight stack can be folded.",
+                short(&commit.node)
+            ));
+        }
+
+        // walk down from the only commit that isn't a parent of another one in the range
+        let in_range = |node: &str| commits.iter().any(|commit| commit.node == node);
+        let is_parent = |node: &str| {
+            commits
+                .iter()
+                .any(|commit| commit.parents.iter().any(|parent| parent == node))
+        };
+        let tops: Vec<&RangeCommit> = commits
+            .iter()
+            .filter(|commit| !is_parent(&commit.node))
+            .collect();
+        let [top] = tops[..] else {
+            return Err("The selection branches: only a straight stack can be folded.".to_string());
+        };
+        let mut stack = vec![top.clone()];
+        while let Some(parent) = stack.last().unwrap().parents.first() {
+            if !in_range(parent) {
+                break;
+            }
+            let parent = commits
+                .iter()
+                .find(|commit| &commit.node == parent)
+                .unwrap();
+            stack.push(parent.clone());
+        }
+        if stack.len() != commits.len() {
+            return Err("The selection branches: only a straight stack can be folded.".to_string());
+        }
+        stack.reverse();
+        Ok(Self { commits: stack })
+    }
+
+    pub fn bottom(&self) -> &RangeCommit {
+        self.commits.first().unwrap()
+    }
+
+    pub fn top(&self) -> &RangeCommit {
+        self.commits.last().unwrap()
+    }
+
+    /// The `sl fold` arguments, without the message. A range ending at the working copy is
+    /// folded `--from` its bottom, any other `--exact`ly.
+    pub fn fold_args(&self) -> Vec<String> {
+        let bottom = self.bottom().node.clone();
+        if self.top().working_copy {
+            vec![
+                "fold".to_string(),
+                "--from".to_string(),
+                "-r".to_string(),
+                bottom,
+            ]
+        } else {
+            let revset = format!("{}::{}", bottom, self.top().node);
+            vec![
+                "fold".to_string(),
+                "--exact".to_string(),
+                "-r".to_string(),
+                revset,
+            ]
+        }
+    }
+}
+
+fn short(node: &str) -> &str {
+    &node[..node.len().min(9)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(node: &str, phase: &str, parents: &[&str]) -> RangeCommit {
+        RangeCommit {
+            node: node.to_string(),
+            phase: phase.to_string(),
+            working_copy: false,
+            parents: parents.iter().map(|parent| parent.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let output = "aaa\u{1f}draft\u{1f}@\u{1f}bbb\u{1f}0000000000\u{1e}\n\
+                      bbb\u{1f}public\u{1f}o\u{1f}ccc\u{1f}\u{1e}";
+        let commits = RangeCommit::parse_list(output);
+        assert_eq!(commits.len(), 2);
+        assert!(commits[0].working_copy);
+        assert_eq!(commits[0].parents, vec!["bbb"]);
+        assert_eq!(commits[1].phase, "public");
+        assert!(RangeCommit::parse_list("").is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        // listed in any order
+        let range = FoldRange::validate(vec![
+            commit("ccc", "draft", &["bbb"]),
+            commit("aaa", "draft", &["base"]),
+            commit("bbb", "draft", &["aaa"]),
+        ])
+        .unwrap();
+        let nodes: Vec<&str> = range.commits.iter().map(|c| c.node.as_str()).collect();
+        assert_eq!(nodes, vec!["aaa", "bbb", "ccc"]);
+        assert_eq!(range.fold_args(), vec!["fold", "--exact", "-r", "aaa::ccc"]);
+    }
+
+    #[test]
+    fn test_validate_working_copy() {
+        let mut top = commit("bbb", "draft", &["aaa"]);
+        top.working_copy = true;
+        let range = FoldRange::validate(vec![commit("aaa", "draft", &["base"]), top]).unwrap();
+        assert_eq!(range.fold_args(), vec!["fold", "--from", "-r", "aaa"]);
+    }
+
+    #[test]
+    fn test_validate_errors() {
+        let error = |commits| FoldRange::validate(commits).unwrap_err();
+        assert!(error(vec![]).contains("same stack"));
+        assert!(error(vec![commit("aaa", "draft", &[])]).contains("at least two"));
+        assert!(error(vec![
+            commit("aaa", "public", &[]),
+            commit("bbb", "draft", &["aaa"])
+        ])
+        .contains("aaa is public"));
+        assert!(error(vec![
+            commit("aaa", "draft", &[]),
+            commit("bbb", "draft", &["aaa", "other"])
+        ])
+        .contains("merge"));
+        // a fork shows up as two commits with no child in the range
+        assert!(error(vec![
+            commit("aaa", "draft", &[]),
+            commit("bbb", "draft", &["aaa"]),
+            commit("ccc", "draft", &["aaa"]),
+        ])
+        .contains("branches"));
+    }
+}
@@ -0,0 +1,267 @@
+//! Drawing the screen a frame at a time.
+//!
+//! Each render lays out a whole [`Frame`], and the [`Screen`] writes only the rows that differ
+//! from the frame it drew last, in a single write. Moving the selection redraws the few rows
+//! it leaves and enters rather than the whole screen, which avoids flicker and keeps the output
+//! small over slow connections.
+//!
+//! Frames are drawn either on the whole screen or inline, on the rows below the shell's output,
+//! which are cleared row by row so the output above stays.
+//!
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor::MoveTo,
+    terminal::{Clear, ClearType},
+    QueueableCommand,
+};
+
+/// The text of a screen, as rows of text drawn at a column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    columns: u16,
+    rows: Vec<Vec<(u16, String)>>,
+}
+impl Frame {
+    /// An empty frame for a screen of `columns` by `rows`.
+    pub fn new(columns: u16, rows: u16) -> Self {
+        Self {
+            columns,
+            rows: vec![Vec::new(); rows as usize],
+        }
+    }
+
+    pub fn columns(&self) -> u16 {
+        self.columns
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Draw `text` from the start of `row`, replacing what the row had.
+    /// Rows past the bottom of the screen are ignored.
+    pub fn line(&mut self, row: usize, text: impl Into<String>) {
+        if let Some(row) = self.rows.get_mut(row) {
+            *row = vec![(0, text.into())];
+        }
+    }
+
+    /// Draw `text` from `column` of `row`, over what the row already has, e.g. for a box
+    /// drawn over the smartlog.
+    pub fn put(&mut self, row: usize, column: u16, text: impl Into<String>) {
+        if let Some(row) = self.rows.get_mut(row) {
+            row.push((column, text.into()));
+        }
+    }
+}
+
+/// The terminal screen, and the frame last drawn on it.
+#[derive(Debug, Default)]
+pub struct Screen {
+    shown: Option<Frame>,
+    /// The terminal row frames are drawn from when drawn inline, `None` on the whole screen.
+    inline_top: Option<u16>,
+}
+impl Screen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A screen drawing frames inline, from the terminal row `top` down.
+    pub fn inline(top: u16) -> Self {
+        Self {
+            shown: None,
+            inline_top: Some(top),
+        }
+    }
+
+    /// The terminal row frames are drawn from when drawn inline.
+    pub fn inline_top(&self) -> Option<u16> {
+        self.inline_top
+    }
+
+    /// Forget what's on the screen to redraw all of it next time, e.g. after another program
+    /// used the terminal.
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    /// Draw `frame` to `out` with a single write and flush, writing only the rows that changed
+    /// since the last frame. Returns the number of bytes written.
+    pub fn draw(&mut self, frame: Frame, out: &mut impl Write) -> io::Result<usize> {
+        let bytes = diff(self.shown.as_ref(), &frame, self.inline_top)?;
+        out.write_all(&bytes)?;
+        out.flush()?;
+        self.shown = Some(frame);
+        Ok(bytes.len())
+    }
+}
+
+/// The output turning the `shown` frame into the `next` one, or drawing it on a cleared screen
+/// if nothing is known to be shown or the screen was resized. Inline frames are drawn from the
+/// `inline_top` row, clearing their rows only.
+fn diff(shown: Option<&Frame>, next: &Frame, inline_top: Option<u16>) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let shown =
+        shown.filter(|shown| shown.columns == next.columns && shown.rows.len() == next.rows.len());
+    let clear_screen = shown.is_none() && inline_top.is_none();
+    if clear_screen {
+        out.queue(Clear(ClearType::All))?;
+    }
+    let top = inline_top.unwrap_or(0);
+    for (idx, row) in next.rows.iter().enumerate() {
+        let y = top + idx as u16;
+        // the cursor is at the start of the row, once it's cleared
+        let cleared = match shown {
+            Some(shown) if shown.rows[idx] == *row => continue,
+            // the whole screen was cleared already
+            None if clear_screen && row.is_empty() => continue,
+            None if clear_screen => false,
+            _ => {
+                out.queue(MoveTo(0, y))?;
+                out.queue(Clear(ClearType::CurrentLine))?;
+                true
+            }
+        };
+        for (segment, (column, text)) in row.iter().enumerate() {
+            if !(cleared && segment == 0 && *column == 0) {
+                out.queue(MoveTo(*column, y))?;
+            }
+            out.write_all(text.as_bytes())?;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAGENTA: &str = "\u{1b}[0;35m";
+    const RESET: &str = "\u{1b}[0m";
+
+    /// The frame of a smartlog of `commits` two-line commits, with `selected` highlighted.
+    fn smartlog_frame(commits: usize, selected: usize) -> Frame {
+        let mut frame = Frame::new(100, 40);
+        for commit in 0..commits {
+            let color = if commit == selected { MAGENTA } else { "" };
+            frame.line(
+                commit * 2,
+                format!(
+                    "o  {}\u{1b}[0;93;1m{:09x}{}{}  Dec 08 at 09:46  royrothenberg{}",
+                    color, commit, RESET, color, RESET
+                ),
+            );
+            frame.line(
+                commit * 2 + 1,
+                format!("│  {}fix the parser, part {}{}", color, commit, RESET),
+            );
+        }
+        frame.line(39, "up/k up  down/j down  enter select  ? help  q quit");
+        frame
+    }
+
+    fn draw(screen: &mut Screen, frame: Frame) -> Vec<u8> {
+        let mut out = Vec::new();
+        screen.draw(frame, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_first_frame() {
+        let mut screen = Screen::new();
+        let mut frame = Frame::new(20, 3);
+        frame.line(0, "first");
+        frame.line(2, "third");
+        assert_eq!(
+            String::from_utf8(draw(&mut screen, frame)).unwrap(),
+            "\u{1b}[2J\u{1b}[1;1Hfirst\u{1b}[3;1Hthird"
+        );
+    }
+
+    #[test]
+    fn test_changed_rows() {
+        let mut screen = Screen::new();
+        let mut frame = Frame::new(20, 3);
+        frame.line(0, "first");
+        frame.line(1, "second");
+        draw(&mut screen, frame.clone());
+
+        // nothing changed
+        assert!(draw(&mut screen, frame.clone()).is_empty());
+
+        frame.line(1, "2nd");
+        frame.line(2, "third");
+        assert_eq!(
+            String::from_utf8(draw(&mut screen, frame.clone())).unwrap(),
+            "\u{1b}[2;1H\u{1b}[2K2nd\u{1b}[3;1H\u{1b}[2Kthird"
+        );
+
+        // a row left empty is cleared
+        let mut next = Frame::new(20, 3);
+        next.line(0, "first");
+        next.line(1, "2nd");
+        assert_eq!(
+            String::from_utf8(draw(&mut screen, next)).unwrap(),
+            "\u{1b}[3;1H\u{1b}[2K"
+        );
+    }
+
+    #[test]
+    fn test_put() {
+        let mut screen = Screen::new();
+        let mut frame = Frame::new(20, 1);
+        frame.line(0, "smartlog");
+        frame.put(0, 4, "│ box │");
+        frame.put(1, 4, "past the bottom");
+        assert_eq!(frame.height(), 1);
+        assert_eq!(
+            String::from_utf8(draw(&mut screen, frame)).unwrap(),
+            "\u{1b}[2J\u{1b}[1;1Hsmartlog\u{1b}[1;5H│ box │"
+        );
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut screen = Screen::new();
+        draw(&mut screen, smartlog_frame(5, 0));
+        let mut resized = smartlog_frame(5, 0);
+        resized.columns = 80;
+        assert!(draw(&mut screen, resized).starts_with(b"\x1b[2J"));
+
+        screen.invalidate();
+        assert!(draw(&mut screen, smartlog_frame(5, 0)).starts_with(b"\x1b[2J"));
+    }
+
+    #[test]
+    fn test_inline() {
+        let mut screen = Screen::inline(30);
+        let mut frame = Frame::new(20, 2);
+        frame.line(0, "first");
+        // the rows are cleared one by one, leaving the rest of the screen
+        assert_eq!(
+            String::from_utf8(draw(&mut screen, frame.clone())).unwrap(),
+            "\u{1b}[31;1H\u{1b}[2Kfirst\u{1b}[32;1H\u{1b}[2K"
+        );
+        frame.put(1, 2, "second");
+        assert_eq!(
+            String::from_utf8(draw(&mut screen, frame)).unwrap(),
+            "\u{1b}[32;1H\u{1b}[2K\u{1b}[32;3Hsecond"
+        );
+    }
+
+    #[test]
+    fn test_navigation_bytes() {
+        let mut screen = Screen::new();
+        let full = draw(&mut screen, smartlog_frame(19, 0)).len();
+        // moving the selection down redraws the four rows of the two commits
+        let step = draw(&mut screen, smartlog_frame(19, 1)).len();
+        assert!(
+            step * 5 < full,
+            "{} bytes per step, {} per frame",
+            step,
+            full
+        );
+    }
+}
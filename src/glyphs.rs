@@ -0,0 +1,179 @@
+//! The characters Sapling draws the smartlog graph with, for each of its graph renderers.
+//!
+//! The graph is drawn in columns two characters wide: a node or edge at the even positions,
+//! and a space or a connecting line at the odd ones. The text of a commit starts at an odd
+//! position right after the graph, which tells the graph apart from text with the same
+//! characters in it.
+//!
+
+/// The characters that stand for a commit, whatever the renderer.
+const NODES: [char; 5] = ['@', 'o', 'x', '%', '*'];
+
+/// The characters of one of Sapling's graph renderers.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GlyphSet {
+    pub name: &'static str,
+    /// Characters drawn in the node columns, besides the nodes themselves.
+    pub edges: &'static [char],
+    /// Characters drawn between the node columns, to join them.
+    pub joins: &'static [char],
+    /// Characters that only this renderer draws, used to recognize its output.
+    distinctive: &'static [char],
+}
+
+/// The default renderer, with rounded corners.
+pub const CURVED: GlyphSet = GlyphSet {
+    name: "curved",
+    edges: &[
+        '│', '─', '╭', '╮', '╯', '╰', '├', '┤', '┬', '┴', '┼', '╷', '╵', '~', ':',
+    ],
+    joins: &['─', '╮', '╯', '┬', '┴', '┼'],
+    distinctive: &['╭', '╮', '╯', '╰'],
+};
+
+/// The box drawing renderer with square corners.
+pub const SQUARE: GlyphSet = GlyphSet {
+    name: "square",
+    edges: &[
+        '│', '─', '┌', '┐', '┘', '└', '├', '┤', '┬', '┴', '┼', '╷', '╵', '~', ':',
+    ],
+    joins: &['─', '┐', '┘', '┬', '┴', '┼'],
+    distinctive: &['┌', '┐', '┘', '└'],
+};
+
+/// The ASCII renderer.
+pub const ASCII: GlyphSet = GlyphSet {
+    name: "ascii",
+    edges: &['|', '/', '\\', '-', '+', '~', ':'],
+    joins: &['/', '\\', '-', '+'],
+    distinctive: &['|', '/', '\\'],
+};
+
+pub const ALL: [&GlyphSet; 3] = [&CURVED, &SQUARE, &ASCII];
+
+impl GlyphSet {
+    /// Guess the renderer that drew `lines`, from the first line drawn with characters only
+    /// one renderer uses. Falls back to the default curved renderer.
+    pub fn detect(lines: &[String]) -> &'static GlyphSet {
+        lines
+            .iter()
+            .find_map(|line| Self::detect_line(line))
+            .unwrap_or(&CURVED)
+    }
+
+    /// The renderer that drew `line`, without its escape codes, if its graph has characters
+    /// only one renderer uses.
+    pub fn detect_line(line: &str) -> Option<&'static GlyphSet> {
+        ALL.into_iter().find(|glyphs| {
+            let (graph, _) = glyphs.split_graph(line);
+            graph.chars().any(|c| glyphs.distinctive.contains(&c))
+        })
+    }
+
+    /// Whether the graph of `line`, without its escape codes, is drawn with box characters,
+    /// as by the curved and square renderers alike, unless it has no corners.
+    ///
+    /// ```
+    /// # use sl_up::glyphs::GlyphSet;
+    /// assert!(GlyphSet::is_box_drawn("│  fix the parser"));
+    /// assert!(!GlyphSet::is_box_drawn(": o  2f85065e7"));
+    /// ```
+    pub fn is_box_drawn(line: &str) -> bool {
+        let (graph, _) = CURVED.split_graph(line);
+        graph
+            .chars()
+            .any(|c| CURVED.edges.contains(&c) && !ASCII.edges.contains(&c))
+    }
+
+    pub fn is_node(&self, c: char) -> bool {
+        NODES.contains(&c)
+    }
+
+    fn is_graph_column(&self, c: char) -> bool {
+        c == ' ' || self.is_node(c) || self.edges.contains(&c)
+    }
+
+    fn is_join_column(&self, c: char) -> bool {
+        c == ' ' || self.joins.contains(&c)
+    }
+
+    /// Split a line without escape codes into its graph, up to its last graph character,
+    /// and the rest of the line.
+    ///
+    /// ```
+    /// # use sl_up::glyphs::CURVED;
+    /// assert_eq!(CURVED.split_graph("╷ o  2f85065e7"), ("╷ o", "  2f85065e7"));
+    /// assert_eq!(CURVED.split_graph("│  x marks the spot"), ("│", "  x marks the spot"));
+    /// ```
+    pub fn split_graph<'a>(&self, line: &'a str) -> (&'a str, &'a str) {
+        let mut end = 0;
+        for (column, (idx, c)) in line.char_indices().enumerate() {
+            let is_graph = if column % 2 == 0 {
+                self.is_graph_column(c)
+            } else {
+                self.is_join_column(c)
+            };
+            if !is_graph {
+                break;
+            }
+            if c != ' ' {
+                end = idx + c.len_utf8();
+            }
+        }
+        line.split_at(end)
+    }
+
+    /// The node drawn in `graph`, if it's the graph of a commit's first line.
+    pub fn node_in(&self, graph: &str) -> Option<char> {
+        graph.chars().step_by(2).find(|c| self.is_node(*c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_detect() {
+        let curved = lines(&["  o  abc", "╭─╯  message", "│"]);
+        assert_eq!(GlyphSet::detect(&curved).name, "curved");
+        let square = lines(&["  o  abc", "┌─┘  message", "│"]);
+        assert_eq!(GlyphSet::detect(&square).name, "square");
+        let ascii = lines(&["  o  abc", " /   message", "|"]);
+        assert_eq!(GlyphSet::detect(&ascii).name, "ascii");
+        // text with a slash doesn't count
+        let plain = lines(&["o  abc", "│  a/b|c"]);
+        assert_eq!(GlyphSet::detect(&plain).name, "curved");
+        assert_eq!(GlyphSet::detect(&[]).name, "curved");
+        assert_eq!(GlyphSet::detect_line("│ o  abc"), None);
+        assert_eq!(GlyphSet::detect_line("|/"), Some(&ASCII));
+    }
+
+    #[test]
+    fn test_split_graph() {
+        assert_eq!(CURVED.split_graph("  @  1cee5d55e"), ("  @", "  1cee5d55e"));
+        assert_eq!(CURVED.split_graph("╭─╯  [pr] fix"), ("╭─╯", "  [pr] fix"));
+        assert_eq!(CURVED.split_graph("│"), ("│", ""));
+        assert_eq!(CURVED.split_graph("~"), ("~", ""));
+        assert_eq!(CURVED.split_graph(""), ("", ""));
+        assert_eq!(SQUARE.split_graph("├─┘  message"), ("├─┘", "  message"));
+        assert_eq!(ASCII.split_graph(" /   message"), (" /", "   message"));
+        assert_eq!(ASCII.split_graph("|\\"), ("|\\", ""));
+        assert_eq!(ASCII.split_graph(": o  2f85065e7"), (": o", "  2f85065e7"));
+        // text at an odd column ends the graph, even if it looks like a node
+        assert_eq!(ASCII.split_graph("|  o-ring"), ("|", "  o-ring"));
+    }
+
+    #[test]
+    fn test_node_in() {
+        assert_eq!(CURVED.node_in("  @"), Some('@'));
+        assert_eq!(CURVED.node_in("╷ o"), Some('o'));
+        assert_eq!(ASCII.node_in("| x"), Some('x'));
+        assert_eq!(CURVED.node_in("╭─╯"), None);
+        assert_eq!(CURVED.node_in("│"), None);
+    }
+}
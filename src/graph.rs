@@ -5,23 +5,117 @@
 //! In our UI, we want to render the exact output of the smartlog. The interactivity we add to the graph makes only commits selectable and actionable.
 //! We render the glyphs as well, but they are not made selectable.
 //!
+use std::borrow::Cow;
+
 use enum_dispatch::enum_dispatch;
 
 const LOCAL_COMMIT_HASH_COLOR: &str = "\u{1b}[0;93;1m";
 const REMOTE_COMMIT_HASH_COLOR: &str = "\u{1b}[0;33m";
+/// The symbols Sapling uses to show the CI status of a pull request.
+const CI_SIGNALS: [&str; 4] = ["✓", "✗", "‼", "⋯"];
+/// The color Sapling uses to highlight the working copy commit.
+pub const SAPLING_SELECTION_COLOR: &str = "\u{1b}[0;35m";
+
+/// What the node Sapling drew for a commit says about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommitState {
+    #[default]
+    Normal,
+    /// Rewritten by an amend, rebase or fold, or hidden, drawn as `x`.
+    Obsolete,
+    /// The working copy's parent, drawn as `@`.
+    WorkingCopy,
+    /// The other side of a merge left with conflicts, drawn as `%`.
+    Conflicted,
+}
+impl CommitState {
+    /// The state shown by a graph node character.
+    ///
+    /// ```
+    /// # use sl_up::graph::CommitState;
+    /// assert_eq!(CommitState::from_node('x'), CommitState::Obsolete);
+    /// assert_eq!(CommitState::from_node('o'), CommitState::Normal);
+    /// ```
+    pub fn from_node(node: char) -> Self {
+        match node {
+            '@' => Self::WorkingCopy,
+            'x' => Self::Obsolete,
+            '%' => Self::Conflicted,
+            _ => Self::Normal,
+        }
+    }
+
+    /// Why going to a commit in this state deserves a second thought, if it does.
+    pub fn goto_warning(&self) -> Option<&'static str> {
+        match self {
+            Self::Obsolete => Some("is obsolete: it was amended, rebased or folded since"),
+            Self::Conflicted => Some("is part of a merge with unresolved conflicts"),
+            Self::Normal | Self::WorkingCopy => None,
+        }
+    }
+}
 
 /// A graph item representing a commit in the smartlog output. It can be selected and deselected.
+///
+/// The lines stay as Sapling printed them: the selection is drawn over them when rendering.
 #[derive(Debug)]
 pub struct Commit {
     lines: Vec<Vec<String>>,
     pub selected: bool,
+    pub state: CommitState,
+    /// Whether Sapling printed this commit selected, in its own selection color.
+    sapling_selected: bool,
 }
 impl Commit {
     pub fn new(parsed_lines: Vec<Vec<String>>, selected: bool) -> Self {
         Self {
             lines: parsed_lines,
             selected,
+            state: CommitState::default(),
+            sapling_selected: selected,
+        }
+    }
+
+    pub fn with_state(mut self, state: CommitState) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Whether Sapling printed this commit selected, in its own selection color.
+    pub fn is_sapling_selected(&self) -> bool {
+        self.sapling_selected
+    }
+
+    /// The lines to draw this commit with, before any highlighting: as Sapling printed them,
+    /// without Sapling's own selection color unless `keep_sapling_selection`.
+    ///
+    /// ```
+    ///  # use sl_up::graph::Commit;
+    ///  let commit_lines = vec![
+    ///      vec!["  @  ", "\u{1b}[0;35m", "\u{1b}[0;93;1m", "1cee5d55e", "\u{1b}[0m", "\u{1b}[0;35m", "  Dec 08 at 09:46", "\u{1b}[0m"],
+    ///  ].iter().map(|x| x.iter().map(|x| x.to_string()).collect()).collect();
+    ///  let commit = Commit::new(commit_lines, true);
+    ///  assert_eq!(commit.display_lines(true)[0].len(), 8);
+    ///  assert_eq!(
+    ///      commit.display_lines(false)[0].concat(),
+    ///      "  @  \u{1b}[0;93;1m1cee5d55e\u{1b}[0m  Dec 08 at 09:46\u{1b}[0m"
+    ///  );
+    /// ```
+    pub fn display_lines(&self, keep_sapling_selection: bool) -> Cow<'_, [Vec<String>]> {
+        if !self.sapling_selected || keep_sapling_selection {
+            return Cow::Borrowed(&self.lines);
         }
+        Cow::Owned(
+            self.lines
+                .iter()
+                .map(|line| {
+                    line.iter()
+                        .filter(|text| !text.contains(SAPLING_SELECTION_COLOR))
+                        .cloned()
+                        .collect()
+                })
+                .collect(),
+        )
     }
 
     /// Get the hash of this commit which can be used for operations such as `sl goto <hash>`
@@ -36,68 +130,62 @@ impl Commit {
     /// ```
     ///
     pub fn hash(&self) -> Option<&str> {
-        let first_line = self.parsed_lines().first().unwrap();
-
-        let mut commit_hash_index = 0;
-        for (index, text) in first_line.iter().enumerate() {
-            if (text == REMOTE_COMMIT_HASH_COLOR) | (text == LOCAL_COMMIT_HASH_COLOR) {
-                commit_hash_index = index + 1;
-                break;
-            }
+        let first_line = self.parsed_lines().first()?;
+        match self.hash_block() {
+            Some(idx) => Some(&first_line[idx]),
+            None => Self::plain_hash(first_line),
         }
-        Some(&first_line[commit_hash_index])
     }
 
-    pub fn select(&mut self) {
-        if self.selected {
-            return;
-        }
-
-        self.selected = true;
-        for line in self.lines.iter_mut() {
-            Self::add_selection_color(line);
-        }
+    /// The index of the block of the first line holding the hash, when Sapling colored it.
+    pub fn hash_block(&self) -> Option<usize> {
+        let first_line = self.parsed_lines().first()?;
+        first_line.iter().enumerate().find_map(|(index, text)| {
+            let is_hash_color =
+                (text == REMOTE_COMMIT_HASH_COLOR) | (text == LOCAL_COMMIT_HASH_COLOR);
+            let hash = first_line.get(index + 1)?;
+            (is_hash_color && is_hash(hash)).then_some(index + 1)
+        })
     }
 
-    pub fn deselect(&mut self) {
-        if !self.selected {
-            return;
-        }
-
-        self.selected = false;
-        for line in self.lines.iter_mut() {
-            Self::remove_selection_color(line);
-        }
+    /// Find the hash of a line without colors, by its position: the first word after the graph,
+    /// which the parser split into the first block.
+    ///
+    /// ```
+    ///  # use sl_up::graph::Commit;
+    ///  let commit_lines = vec![vec!["  @".to_string(), "  1cee5d55e  Dec 08 at 09:46".to_string()]];
+    ///  let commit = Commit::new(commit_lines, false);
+    ///  assert_eq!(commit.hash(), Some("1cee5d55e"));
+    /// ```
+    fn plain_hash(line: &[String]) -> Option<&str> {
+        let hash = line
+            .iter()
+            .skip(1)
+            .filter(|text| !text.starts_with('\u{1b}'))
+            .find_map(|text| text.split_whitespace().next())?;
+        is_hash(hash).then_some(hash)
     }
 
-    fn add_selection_color(line: &mut Vec<String>) {
-        if line.len() > 4 {
-            line.insert(4, Self::selection_formatter());
-        } else if line.len() > 1 {
-            line.insert(1, Self::selection_formatter());
-        } else {
-            let text = line.pop().unwrap();
-            for block in text.splitn(2, ' ') {
-                line.push(block.to_string());
-            }
-            // restore the space we removed with the split above
-            line.insert(1, " ".to_string());
-            line.insert(1, Self::selection_formatter());
-        }
-        line.push(Self::stop_formatter());
+    /// The CI signal Sapling draws after the pull request status (e.g. `✓` or `✗`), if any.
+    pub fn ci_signal(&self) -> Option<&str> {
+        self.parsed_lines()
+            .first()?
+            .iter()
+            .map(|text| text.trim())
+            .find(|text| CI_SIGNALS.contains(text))
     }
 
-    fn remove_selection_color(line: &mut Vec<String>) {
-        line.retain(|text| !text.contains(&Self::selection_formatter()));
+    pub fn select(&mut self) {
+        self.selected = true;
     }
 
-    fn selection_formatter() -> String {
-        "\u{1b}[0;35m".to_string()
+    pub fn deselect(&mut self) {
+        self.selected = false;
     }
+}
 
-    fn stop_formatter() -> String {
-        "\u{1b}[0m".to_string()
-    }
+fn is_hash(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_hexdigit())
 }
 
 /// A graph item representing a glyph in the smartlog output.
@@ -158,10 +246,49 @@ pub enum ItemType {
 #[cfg(test)]
 mod tests {
 
-    use crate::parser::SmartLogParser;
+    use proptest::prelude::*;
+
+    use crate::parser::{tests::smartlog_lines, SmartLogParser};
 
     use super::*;
 
+    proptest! {
+        #[test]
+        fn selection_leaves_lines_as_printed(lines in smartlog_lines()) {
+            for item in SmartLogParser::parse(&lines).unwrap() {
+                let ItemType::Commit(mut commit) = item else {
+                    continue;
+                };
+                let original = commit.to_string_vec();
+                commit.select();
+                prop_assert_eq!(&commit.to_string_vec(), &original);
+                commit.deselect();
+                prop_assert_eq!(&commit.to_string_vec(), &original);
+                prop_assert_eq!(&commit.display_lines(true)[..], commit.parsed_lines());
+                let unselected = commit.display_lines(false);
+                prop_assert_eq!(unselected.len(), original.len());
+                prop_assert!(
+                    commit.is_sapling_selected()
+                        || unselected.iter().map(|line| line.concat()).eq(original)
+                );
+            }
+        }
+
+        #[test]
+        fn hash_is_a_hex_word_of_the_first_line(lines in smartlog_lines()) {
+            for item in SmartLogParser::parse(&lines).unwrap() {
+                let ItemType::Commit(commit) = item else {
+                    continue;
+                };
+                if let Some(hash) = commit.hash() {
+                    prop_assert!(!hash.is_empty());
+                    prop_assert!(hash.chars().all(|c| c.is_ascii_hexdigit()), "{:?}", hash);
+                    prop_assert!(commit.to_string_vec()[0].contains(hash));
+                }
+            }
+        }
+    }
+
     const RAW_LINES: [&str; 15] = [
         "  @  \u{1b}[0;35m\u{1b}[0;93;1m1cee5d55e\u{1b}[0m\u{1b}[0;35m  Dec 08 at 09:46  royrothenberg  \u{1b}[0;36m#780 Closed\u{1b}[0m\u{1b}[0;35m \u{1b}[0;31m✗\u{1b}[0m",
         "  │  \u{1b}[0;35m[pr body update] update stack list without overwriting PR title and body\u{1b}[0m",
@@ -237,56 +364,63 @@ mod tests {
     fn test_select() {
         let graph_items = &mut SmartLogParser::parse(&raw_lines()).unwrap();
 
-        let commit = &mut graph_items[2];
-        match commit {
-            ItemType::Commit(commit) => {
-                assert!(!commit.selected);
-                assert_eq!(
-                    commit.parsed_lines()[0][4],
-                    "  Dec 08 at 09:46  royrothenberg  "
-                );
-                assert_eq!(
-                    commit.parsed_lines()[1][1],
-                    "  [pr body update] fix reviewstack option breaking stack list detection"
-                );
-                commit.select();
-                assert!(commit.selected);
-                // verifying idempotency
-                commit.select();
-                assert!(commit.selected);
-                assert_eq!(
-                    commit.parsed_lines()[0][4],
-                    "\u{1b}[0;35m", // This item was inserted by select()
-                );
-                assert_eq!(
-                    commit.parsed_lines()[1][1],
-                    "\u{1b}[0;35m", // This item was inserted by select()
-                );
-            }
-            _ => panic!("Expected GraphCommit"),
-        }
+        let ItemType::Commit(commit) = &mut graph_items[2] else {
+            panic!("Expected GraphCommit");
+        };
+        let original = commit.parsed_lines().clone();
+        assert!(!commit.selected);
+        assert!(!commit.is_sapling_selected());
+        commit.select();
+        assert!(commit.selected);
+        // verifying idempotency
+        commit.select();
+        assert!(commit.selected);
+        // the selection is drawn when rendering
+        assert_eq!(commit.parsed_lines(), &original);
+        assert_eq!(&commit.display_lines(false)[..], &original[..]);
     }
 
     #[test]
     fn test_deselect() {
         let graph_items = &mut SmartLogParser::parse(&raw_lines()).unwrap();
 
-        let commit = &mut graph_items[0];
-        match commit {
-            ItemType::Commit(commit) => {
-                assert!(commit.selected);
-                assert!(commit.parsed_lines()[0].contains(&"\u{1b}[0;35m".to_string()));
-                assert!(commit.parsed_lines()[1].contains(&"\u{1b}[0;35m".to_string()));
-                commit.deselect();
-                assert!(!commit.selected);
-                // verifying idempotency
-                commit.deselect();
-                assert!(!commit.selected);
-                assert!(!commit.parsed_lines()[0].contains(&"\u{1b}[0;35m".to_string()));
-                assert!(!commit.parsed_lines()[1].contains(&"\u{1b}[0;35m".to_string()));
-            }
-            _ => panic!("Expected GraphCommit"),
-        }
+        let ItemType::Commit(commit) = &mut graph_items[0] else {
+            panic!("Expected GraphCommit");
+        };
+        let original = commit.parsed_lines().clone();
+        assert!(commit.selected);
+        assert!(commit.is_sapling_selected());
+        commit.deselect();
+        assert!(!commit.selected);
+        // verifying idempotency
+        commit.deselect();
+        assert!(!commit.selected);
+        assert_eq!(commit.parsed_lines(), &original);
+        assert!(commit.is_sapling_selected());
+    }
+
+    #[test]
+    fn test_display_lines() {
+        let graph_items = &mut SmartLogParser::parse(&raw_lines()).unwrap();
+        let sapling_color = SAPLING_SELECTION_COLOR.to_string();
+
+        let ItemType::Commit(selected) = &graph_items[0] else {
+            panic!("Expected GraphCommit");
+        };
+        assert_eq!(
+            &selected.display_lines(true)[..],
+            &selected.parsed_lines()[..]
+        );
+        let unselected = selected.display_lines(false);
+        assert!(!unselected[0].contains(&sapling_color));
+        assert!(!unselected[1].contains(&sapling_color));
+        assert_eq!(unselected[0][1], "\u{1b}[0;93;1m");
+        assert_eq!(unselected[0][2], "1cee5d55e");
+
+        let ItemType::Commit(commit) = &graph_items[2] else {
+            panic!("Expected GraphCommit");
+        };
+        assert_eq!(&commit.display_lines(false)[..], &commit.parsed_lines()[..]);
     }
 
     #[test]
@@ -297,6 +431,7 @@ mod tests {
         match local_commit {
             ItemType::Commit(commit) => {
                 assert_eq!(commit.hash().unwrap(), "1cee5d55e");
+                assert_eq!(commit.hash_block(), Some(3));
             }
             _ => panic!("Expected GraphCommit"),
         }
@@ -305,11 +440,25 @@ mod tests {
         match remote_commit {
             ItemType::Commit(commit) => {
                 assert_eq!(commit.hash().unwrap(), "ba27d4d13");
+                assert_eq!(commit.hash_block(), Some(2));
             }
             _ => panic!("Expected GraphCommit"),
         }
     }
 
+    #[test]
+    fn test_ci_signal() {
+        let graph_items = &mut SmartLogParser::parse(&raw_lines()).unwrap();
+        let signals: Vec<Option<&str>> = graph_items
+            .iter()
+            .filter_map(|item| match item {
+                ItemType::Commit(commit) => Some(commit.ci_signal()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(signals, vec![Some("✗"), Some("✗"), None, Some("✓"), None]);
+    }
+
     fn raw_lines() -> Vec<String> {
         RAW_LINES.iter().map(|x| x.to_string()).collect()
     }
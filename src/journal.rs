@@ -0,0 +1,175 @@
+//! Recent moves of the working copy and bookmarks, as recorded by `sl journal`.
+//!
+//! Each entry can be previewed with the smartlog as it was right after it, and restored by
+//! moving the working copy or bookmark back where the entry left it.
+//!
+/// Separates the fields of an entry in the template output.
+const FIELD_SEPARATOR: char = '\u{1f}';
+/// Separates entries in the template output.
+const RECORD_SEPARATOR: char = '\u{1e}';
+const FIELDS: [&str; 5] = [
+    "{date|isodate}",
+    "{command}",
+    "{name}",
+    "{oldnodes}",
+    "{newnodes}",
+];
+/// The name the journal records working copy moves under.
+const WORKING_COPY: &str = ".";
+
+/// The template passed to `sl journal -T`.
+pub fn template() -> String {
+    let mut template = FIELDS.join(&FIELD_SEPARATOR.to_string());
+    template.push(RECORD_SEPARATOR);
+    template
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub date: String,
+    /// The command that made the move, e.g. `sl goto main`.
+    pub command: String,
+    /// `.` for the working copy, or the name of a bookmark.
+    pub name: String,
+    pub old_nodes: Vec<String>,
+    pub new_nodes: Vec<String>,
+}
+impl JournalEntry {
+    /// Parse the output of [`template`], newest entries first.
+    pub fn parse_list(output: &str) -> Vec<Self> {
+        output
+            .split(RECORD_SEPARATOR)
+            .filter_map(Self::parse)
+            .collect()
+    }
+
+    fn parse(record: &str) -> Option<Self> {
+        let mut fields = record.trim_start_matches('\n').split(FIELD_SEPARATOR);
+        let mut next = || fields.next().map(|field| field.to_string());
+        let nodes = |field: String| -> Vec<String> {
+            field
+                .split([',', ' '])
+                .filter(|node| !node.is_empty())
+                .map(String::from)
+                .collect()
+        };
+        let entry = Self {
+            date: next()?,
+            command: next()?,
+            name: next()?,
+            old_nodes: nodes(next()?),
+            new_nodes: nodes(next()?),
+        };
+        if entry.name.is_empty() {
+            return None;
+        }
+        Some(entry)
+    }
+
+    pub fn is_working_copy(&self) -> bool {
+        self.name == WORKING_COPY
+    }
+
+    /// The commit the entry left the working copy or bookmark on, if it still existed.
+    pub fn node(&self) -> Option<&str> {
+        self.new_nodes.first().map(String::as_str)
+    }
+
+    /// A one-line summary for the journal list.
+    pub fn summary(&self) -> String {
+        let target = if self.is_working_copy() {
+            "working copy".to_string()
+        } else {
+            format!("bookmark {}", self.name)
+        };
+        let node = self.node().unwrap_or("(deleted)");
+        let node: String = node.chars().take(9).collect();
+        format!("{}  {:<9}  {}  {}", self.date, node, target, self.command)
+    }
+
+    /// The `sl` arguments that move the bookmark back where this entry left it.
+    /// The working copy is restored with a goto instead, which has its own checks.
+    pub fn bookmark_restore_args(&self) -> Option<Vec<String>> {
+        if self.is_working_copy() {
+            return None;
+        }
+        let args = match self.node() {
+            Some(node) => vec!["bookmark", "--force", "--rev", node, &self.name],
+            None => vec!["bookmark", "--delete", &self.name],
+        };
+        Some(args.into_iter().map(String::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(fields: &[&str]) -> String {
+        let mut record = fields.join(&FIELD_SEPARATOR.to_string());
+        record.push(RECORD_SEPARATOR);
+        record
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let output = record(&[
+            "2023-12-08 09:46 -0800",
+            "sl goto main",
+            ".",
+            "1cee5d55e",
+            "ba27d4d13",
+        ]) + &record(&[
+            "2023-12-07 22:20 -0800",
+            "sl bookmark -d feature",
+            "feature",
+            "c3bd9e5fa",
+            "",
+        ]);
+        let entries = JournalEntry::parse_list(&output);
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].is_working_copy());
+        assert_eq!(entries[0].old_nodes, vec!["1cee5d55e"]);
+        assert_eq!(entries[0].node(), Some("ba27d4d13"));
+        assert_eq!(entries[1].name, "feature");
+        assert_eq!(entries[1].node(), None);
+
+        assert!(JournalEntry::parse_list("").is_empty());
+    }
+
+    #[test]
+    fn test_summary() {
+        let entry = JournalEntry {
+            date: "2023-12-08 09:46 -0800".to_string(),
+            command: "sl goto main".to_string(),
+            name: ".".to_string(),
+            old_nodes: vec!["1cee5d55e".to_string()],
+            new_nodes: vec!["ba27d4d13fedcba".to_string()],
+        };
+        assert_eq!(
+            entry.summary(),
+            "2023-12-08 09:46 -0800  ba27d4d13  working copy  sl goto main"
+        );
+        assert_eq!(entry.bookmark_restore_args(), None);
+    }
+
+    #[test]
+    fn test_bookmark_restore_args() {
+        let mut entry = JournalEntry {
+            date: String::new(),
+            command: "sl bookmark feature".to_string(),
+            name: "feature".to_string(),
+            old_nodes: Vec::new(),
+            new_nodes: vec!["c3bd9e5fa".to_string()],
+        };
+        assert_eq!(
+            entry.bookmark_restore_args().unwrap(),
+            vec!["bookmark", "--force", "--rev", "c3bd9e5fa", "feature"]
+        );
+        entry.new_nodes.clear();
+        assert_eq!(
+            entry.bookmark_restore_args().unwrap(),
+            vec!["bookmark", "--delete", "feature"]
+        );
+    }
+}
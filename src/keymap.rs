@@ -0,0 +1,797 @@
+//! Maps terminal key events to the abstract actions the UI knows how to perform.
+//!
+//! Keys are written in config files as strings such as `"q"`, `"enter"`, `"ctrl-c"` or `"alt-x"`.
+//! A sequence of keys pressed one after the other is separated by spaces, e.g. `"g g"`.
+//! Unless a digit is bound to something, typing a number before a key repeats its action (e.g. `5j`).
+//!
+use std::{collections::HashMap, fmt};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// An action the UI can perform in response to a key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    HalfPageUp,
+    HalfPageDown,
+    PageUp,
+    PageDown,
+    MoveTop,
+    MoveBottom,
+    Select,
+    EditMessage,
+    Fold,
+    ToggleMark,
+    ClearMarks,
+    Graft,
+    Next,
+    Prev,
+    StackTop,
+    StackBottom,
+    Undo,
+    Redo,
+    Journal,
+    CreateBookmark,
+    MoveBookmark,
+    DeleteBookmark,
+    JumpToBookmark,
+    ToggleDetails,
+    Help,
+    Quit,
+}
+impl Action {
+    pub const ALL: [Action; 28] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::HalfPageUp,
+        Action::HalfPageDown,
+        Action::PageUp,
+        Action::PageDown,
+        Action::MoveTop,
+        Action::MoveBottom,
+        Action::Select,
+        Action::EditMessage,
+        Action::Fold,
+        Action::ToggleMark,
+        Action::ClearMarks,
+        Action::Graft,
+        Action::Next,
+        Action::Prev,
+        Action::StackTop,
+        Action::StackBottom,
+        Action::Undo,
+        Action::Redo,
+        Action::Journal,
+        Action::CreateBookmark,
+        Action::MoveBookmark,
+        Action::DeleteBookmark,
+        Action::JumpToBookmark,
+        Action::ToggleDetails,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    /// The name used for this action in config files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::HalfPageUp => "half_page_up",
+            Action::HalfPageDown => "half_page_down",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::MoveTop => "move_top",
+            Action::MoveBottom => "move_bottom",
+            Action::Select => "select",
+            Action::EditMessage => "edit_message",
+            Action::Fold => "fold",
+            Action::ToggleMark => "toggle_mark",
+            Action::ClearMarks => "clear_marks",
+            Action::Graft => "graft",
+            Action::Next => "next",
+            Action::Prev => "prev",
+            Action::StackTop => "stack_top",
+            Action::StackBottom => "stack_bottom",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Journal => "journal",
+            Action::CreateBookmark => "create_bookmark",
+            Action::MoveBookmark => "move_bookmark",
+            Action::DeleteBookmark => "delete_bookmark",
+            Action::JumpToBookmark => "jump_to_bookmark",
+            Action::ToggleDetails => "toggle_details",
+            Action::Help => "help",
+            Action::Quit => "quit",
+        }
+    }
+
+    /// A short explanation of what the action does, shown in the help overlay.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "previous commit",
+            Action::MoveDown => "next commit",
+            Action::HalfPageUp => "half a page up",
+            Action::HalfPageDown => "half a page down",
+            Action::PageUp => "a page up",
+            Action::PageDown => "a page down",
+            Action::MoveTop => "first commit",
+            Action::MoveBottom => "last commit",
+            Action::Select => "run the default action on the commit",
+            Action::EditMessage => "edit the commit's message in $EDITOR",
+            Action::Fold => "fold a stack of commits: press on one end, then on the other",
+            Action::ToggleMark => "mark or unmark the commit",
+            Action::ClearMarks => "unmark every commit",
+            Action::Graft => "graft the marked commits, or this one, onto the working copy",
+            Action::Next => "move the working copy up its stack (sl next)",
+            Action::Prev => "move the working copy down its stack (sl prev)",
+            Action::StackTop => "move the working copy to the top of its stack",
+            Action::StackBottom => "move the working copy to the bottom of its stack",
+            Action::Undo => "undo the last repository operation, after a preview",
+            Action::Redo => "redo the last undone operation, after a preview",
+            Action::Journal => "browse and restore recent working copy and bookmark moves",
+            Action::CreateBookmark => "create a bookmark on the commit",
+            Action::MoveBookmark => "move a bookmark to the commit",
+            Action::DeleteBookmark => "delete a bookmark",
+            Action::JumpToBookmark => "jump to a bookmarked commit",
+            Action::ToggleDetails => "show or hide the commit details panel",
+            Action::Help => "show this help",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn category(&self) -> Category {
+        match self {
+            Action::MoveUp
+            | Action::MoveDown
+            | Action::HalfPageUp
+            | Action::HalfPageDown
+            | Action::PageUp
+            | Action::PageDown
+            | Action::MoveTop
+            | Action::MoveBottom => Category::Navigation,
+            Action::Select
+            | Action::EditMessage
+            | Action::Fold
+            | Action::ToggleMark
+            | Action::ClearMarks
+            | Action::Graft => Category::Commit,
+            Action::Next | Action::Prev | Action::StackTop | Action::StackBottom => Category::Stack,
+            Action::Undo | Action::Redo | Action::Journal => Category::Repository,
+            Action::CreateBookmark
+            | Action::MoveBookmark
+            | Action::DeleteBookmark
+            | Action::JumpToBookmark => Category::Bookmarks,
+            Action::ToggleDetails | Action::Help | Action::Quit => Category::General,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A group of related actions, used to organize the help overlay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Navigation,
+    Commit,
+    Stack,
+    Repository,
+    Bookmarks,
+    General,
+}
+impl Category {
+    pub const ALL: [Category; 6] = [
+        Category::Navigation,
+        Category::Commit,
+        Category::Stack,
+        Category::Repository,
+        Category::Bookmarks,
+        Category::General,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Category::Navigation => "Navigation",
+            Category::Commit => "Commit",
+            Category::Stack => "Stack",
+            Category::Repository => "Repository",
+            Category::Bookmarks => "Bookmarks",
+            Category::General => "General",
+        }
+    }
+}
+
+/// A single key press, including its modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+impl KeyBinding {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    /// Parse a key description such as `"k"`, `"pagedown"` or `"ctrl-c"`.
+    /// ```
+    ///  # use sl_up::keymap::KeyBinding;
+    ///  # use crossterm::event::{KeyCode, KeyModifiers};
+    ///  let binding = KeyBinding::parse("ctrl-c").unwrap();
+    ///  assert_eq!(binding, KeyBinding::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        // a lone "-" is a key of its own, not a modifier separator
+        while let Some((modifier, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "meta" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier '{}' in key '{}'", modifier, text)),
+            };
+            rest = key;
+        }
+
+        let code = match rest.to_lowercase().as_str() {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "enter" | "return" => KeyCode::Enter,
+            "space" => KeyCode::Char(' '),
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            name if name.len() > 1 && name.starts_with('f') => match name[1..].parse::<u8>() {
+                Ok(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                _ => return Err(format!("unknown key '{}'", text)),
+            },
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(format!("unknown key '{}'", text)),
+                }
+            }
+        };
+        Ok(Self::new(code, modifiers).normalized())
+    }
+
+    /// Build a binding from a terminal event, normalizing away modifiers that are
+    /// already implied by the key itself (e.g. shift on an uppercase letter).
+    pub fn from_event(event: &KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers).normalized()
+    }
+
+    fn normalized(mut self) -> Self {
+        if let KeyCode::Char(c) = self.code {
+            if self.modifiers.contains(KeyModifiers::SHIFT) {
+                self.code = KeyCode::Char(c.to_ascii_uppercase());
+                self.modifiers.remove(KeyModifiers::SHIFT);
+            }
+        }
+        self
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            f.write_str("ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            f.write_str("alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            f.write_str("shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::Up => f.write_str("up"),
+            KeyCode::Down => f.write_str("down"),
+            KeyCode::Left => f.write_str("left"),
+            KeyCode::Right => f.write_str("right"),
+            KeyCode::Enter => f.write_str("enter"),
+            KeyCode::Esc => f.write_str("esc"),
+            KeyCode::Tab => f.write_str("tab"),
+            KeyCode::Backspace => f.write_str("backspace"),
+            KeyCode::Delete => f.write_str("delete"),
+            KeyCode::Home => f.write_str("home"),
+            KeyCode::End => f.write_str("end"),
+            KeyCode::PageUp => f.write_str("pageup"),
+            KeyCode::PageDown => f.write_str("pagedown"),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// One or more key presses, typed one after the other.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeySequence(pub Vec<KeyBinding>);
+impl KeySequence {
+    /// Parse a space-separated list of keys, such as `"g g"` or `"ctrl-d"`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let keys = text
+            .split_whitespace()
+            .map(KeyBinding::parse)
+            .collect::<Result<Vec<KeyBinding>, String>>()?;
+        if keys.is_empty() {
+            return Err("empty key".to_string());
+        }
+        Ok(Self(keys))
+    }
+}
+
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys: Vec<String> = self.0.iter().map(|key| key.to_string()).collect();
+        f.write_str(&keys.join(" "))
+    }
+}
+
+/// A built-in set of key bindings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeymapPreset {
+    Default,
+    Vim,
+    Emacs,
+}
+impl KeymapPreset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(KeymapPreset::Default),
+            "vim" => Some(KeymapPreset::Vim),
+            "emacs" => Some(KeymapPreset::Emacs),
+            _ => None,
+        }
+    }
+
+    fn bindings(&self) -> Vec<(Action, Vec<&'static str>)> {
+        let mut bindings = vec![
+            (Action::MoveUp, vec!["up"]),
+            (Action::MoveDown, vec!["down"]),
+            (Action::PageUp, vec!["pageup"]),
+            (Action::PageDown, vec!["pagedown"]),
+            (Action::MoveTop, vec!["home"]),
+            (Action::MoveBottom, vec!["end"]),
+            (Action::Select, vec!["enter", "space"]),
+            (Action::EditMessage, vec!["e"]),
+            (Action::Fold, vec!["f"]),
+            (Action::ToggleMark, vec!["m"]),
+            (Action::ClearMarks, vec!["M"]),
+            (Action::Graft, vec!["c"]),
+            (Action::Next, vec!["]"]),
+            (Action::Prev, vec!["["]),
+            (Action::StackTop, vec!["}"]),
+            (Action::StackBottom, vec!["{"]),
+            (Action::Undo, vec!["u"]),
+            (Action::Redo, vec!["U", "ctrl-r"]),
+            (Action::Journal, vec!["J"]),
+            (Action::CreateBookmark, vec!["b c"]),
+            (Action::MoveBookmark, vec!["b m"]),
+            (Action::DeleteBookmark, vec!["b d"]),
+            (Action::JumpToBookmark, vec!["'", "b g"]),
+            (Action::ToggleDetails, vec!["i"]),
+            (Action::Help, vec!["?"]),
+            (Action::Quit, vec!["q", "esc", "ctrl-c"]),
+        ];
+        match self {
+            KeymapPreset::Default => {}
+            KeymapPreset::Vim => bindings.extend([
+                (Action::MoveUp, vec!["k"]),
+                (Action::MoveDown, vec!["j"]),
+                (Action::HalfPageUp, vec!["ctrl-u"]),
+                (Action::HalfPageDown, vec!["ctrl-d"]),
+                (Action::PageUp, vec!["ctrl-b"]),
+                (Action::PageDown, vec!["ctrl-f"]),
+                (Action::MoveTop, vec!["g g"]),
+                (Action::MoveBottom, vec!["G"]),
+            ]),
+            KeymapPreset::Emacs => bindings.extend([
+                (Action::MoveUp, vec!["ctrl-p"]),
+                (Action::MoveDown, vec!["ctrl-n"]),
+                (Action::PageUp, vec!["alt-v"]),
+                (Action::PageDown, vec!["ctrl-v"]),
+                (Action::MoveTop, vec!["alt-<"]),
+                (Action::MoveBottom, vec!["alt->"]),
+                (Action::Quit, vec!["ctrl-g"]),
+            ]),
+        }
+        bindings
+    }
+}
+
+/// An action and every key sequence bound to it.
+pub type ActionKeys = (Action, Vec<KeySequence>);
+
+/// The set of key bindings for every action.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<KeySequence, Action>,
+}
+impl Keymap {
+    pub fn new() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    pub fn preset(preset: KeymapPreset) -> Self {
+        let mut keymap = Self::new();
+        for (action, keys) in preset.bindings() {
+            for key in keys {
+                keymap.bind(KeySequence::parse(key).unwrap(), action);
+            }
+        }
+        keymap
+    }
+
+    /// Bind `keys` to `action`, returning the action they were previously bound to, if any.
+    pub fn bind(&mut self, keys: KeySequence, action: Action) -> Option<Action> {
+        self.bindings.insert(keys, action)
+    }
+
+    /// Remove every binding of `action`.
+    pub fn unbind_action(&mut self, action: Action) {
+        self.bindings.retain(|_, bound| *bound != action);
+    }
+
+    /// The action bound to a single key press, ignoring multi-key sequences.
+    pub fn action_for(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .get(&KeySequence(vec![KeyBinding::from_event(event)]))
+            .copied()
+    }
+
+    /// All keys bound to `action`, in a stable order.
+    pub fn keys_for(&self, action: Action) -> Vec<KeySequence> {
+        let mut keys: Vec<KeySequence> = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(keys, _)| keys.clone())
+            .collect();
+        // shortest first, as those are the easiest to remember
+        keys.sort_by_key(|keys| (keys.to_string().len(), keys.to_string()));
+        keys
+    }
+
+    /// Every bound action with its keys, grouped by category, for the help overlay.
+    pub fn describe(&self) -> Vec<(Category, Vec<ActionKeys>)> {
+        Category::ALL
+            .into_iter()
+            .map(|category| {
+                let actions = Action::ALL
+                    .into_iter()
+                    .filter(|action| action.category() == category)
+                    .map(|action| (action, self.keys_for(action)))
+                    .filter(|(_, keys)| !keys.is_empty())
+                    .collect();
+                (category, actions)
+            })
+            .filter(|(_, actions): &(Category, Vec<_>)| !actions.is_empty())
+            .collect()
+    }
+
+    /// Whether some binding starts with `keys` and is longer than it.
+    fn is_prefix(&self, keys: &[KeyBinding]) -> bool {
+        self.bindings
+            .keys()
+            .any(|bound| bound.0.len() > keys.len() && bound.0.starts_with(keys))
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::preset(KeymapPreset::Default)
+    }
+}
+
+/// The outcome of feeding a key press to a [`KeyDispatcher`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dispatch {
+    /// The keys typed so far complete a binding: run the action `count` times.
+    Action(Action, usize),
+    /// The keys typed so far are the start of a binding or a count.
+    Pending,
+    /// The keys typed so far aren't bound to anything, and were discarded.
+    Unbound,
+}
+
+/// Tracks multi-key sequences and counts as keys are pressed.
+#[derive(Debug, Default)]
+pub struct KeyDispatcher {
+    pending: Vec<KeyBinding>,
+    count: Option<usize>,
+}
+impl KeyDispatcher {
+    const MAX_COUNT: usize = 9999;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, keymap: &Keymap, event: &KeyEvent) -> Dispatch {
+        let key = KeyBinding::from_event(event);
+
+        if key.code == KeyCode::Esc && self.is_pending() {
+            self.reset();
+            return Dispatch::Unbound;
+        }
+        if self.pending.is_empty() {
+            if let Some(digit) = self.count_digit(keymap, key) {
+                let count = self.count.unwrap_or(0) * 10 + digit;
+                self.count = Some(count.min(Self::MAX_COUNT));
+                return Dispatch::Pending;
+            }
+        }
+
+        self.pending.push(key);
+        if let Some(action) = keymap.bindings.get(&KeySequence(self.pending.clone())) {
+            let count = self.count.unwrap_or(1);
+            self.reset();
+            return Dispatch::Action(*action, count);
+        }
+        if keymap.is_prefix(&self.pending) {
+            return Dispatch::Pending;
+        }
+        self.reset();
+        Dispatch::Unbound
+    }
+
+    /// The digit `key` adds to the count, unless it's bound to something itself.
+    fn count_digit(&self, keymap: &Keymap, key: KeyBinding) -> Option<usize> {
+        let KeyCode::Char(c) = key.code else {
+            return None;
+        };
+        let digit = c.to_digit(10)? as usize;
+        // a leading zero isn't a count
+        if !key.modifiers.is_empty() || (digit == 0 && self.count.is_none()) {
+            return None;
+        }
+        let keys = [key];
+        if keymap.is_prefix(&keys) || keymap.bindings.contains_key(&KeySequence(keys.to_vec())) {
+            return None;
+        }
+        Some(digit)
+    }
+
+    /// Whether a count or the start of a key sequence has been typed.
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty() || self.count.is_some()
+    }
+
+    /// The count and keys typed so far, e.g. `"5g"`.
+    pub fn pending_keys(&self) -> String {
+        let mut text = self
+            .count
+            .map(|count| count.to_string())
+            .unwrap_or_default();
+        for key in self.pending.iter() {
+            text.push_str(&key.to_string());
+        }
+        text
+    }
+
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.count = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            KeyBinding::parse("q").unwrap(),
+            KeyBinding::new(KeyCode::Char('q'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            KeyBinding::parse("Enter").unwrap(),
+            KeyBinding::new(KeyCode::Enter, KeyModifiers::NONE)
+        );
+        assert_eq!(
+            KeyBinding::parse("ctrl-alt-x").unwrap(),
+            KeyBinding::new(
+                KeyCode::Char('x'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            )
+        );
+        assert_eq!(
+            KeyBinding::parse("-").unwrap(),
+            KeyBinding::new(KeyCode::Char('-'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            KeyBinding::parse("f5").unwrap(),
+            KeyBinding::new(KeyCode::F(5), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            KeyBinding::parse("shift-g").unwrap(),
+            KeyBinding::parse("G").unwrap()
+        );
+        assert!(KeyBinding::parse("hyper-x").is_err());
+        assert!(KeyBinding::parse("foo").is_err());
+        assert!(KeyBinding::parse("f13").is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for key in ["q", "space", "ctrl-c", "alt-shift-up", "pagedown", "f1"] {
+            let binding = KeyBinding::parse(key).unwrap();
+            assert_eq!(binding.to_string(), key);
+        }
+    }
+
+    #[test]
+    fn test_default_keymap() {
+        let keymap = Keymap::default();
+        let event = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action_for(&event), Some(Action::Quit));
+        let event = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE);
+        assert_eq!(keymap.action_for(&event), Some(Action::Graft));
+        let event = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE);
+        assert_eq!(keymap.action_for(&event), None);
+        assert_eq!(
+            keymap.keys_for(Action::Select),
+            vec![
+                KeySequence::parse("enter").unwrap(),
+                KeySequence::parse("space").unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        let mut keymap = Keymap::preset(KeymapPreset::Vim);
+        keymap.bind(KeySequence::parse("x").unwrap(), Action::Quit);
+        for action in Action::ALL {
+            if action.category() == Category::Commit {
+                keymap.unbind_action(action);
+            }
+        }
+        let sections = keymap.describe();
+        let categories: Vec<Category> = sections.iter().map(|(category, _)| *category).collect();
+        // nothing is bound in the commit category anymore
+        assert_eq!(
+            categories,
+            vec![
+                Category::Navigation,
+                Category::Stack,
+                Category::Repository,
+                Category::Bookmarks,
+                Category::General
+            ]
+        );
+
+        let (_, general) = sections.last().unwrap();
+        let (action, keys) = general.last().unwrap();
+        assert_eq!(*action, Action::Quit);
+        let keys: Vec<String> = keys.iter().map(|keys| keys.to_string()).collect();
+        assert_eq!(keys, vec!["q", "x", "esc", "ctrl-c"]);
+    }
+
+    #[test]
+    fn test_key_sequence() {
+        let sequence = KeySequence::parse("g  g").unwrap();
+        assert_eq!(sequence.0.len(), 2);
+        assert_eq!(sequence.to_string(), "g g");
+        assert!(KeySequence::parse(" ").is_err());
+        assert!(KeySequence::parse("g foo").is_err());
+    }
+
+    #[test]
+    fn test_presets() {
+        let vim = Keymap::preset(KeymapPreset::Vim);
+        assert_eq!(vim.action_for(&key('j')), Some(Action::MoveDown));
+        assert_eq!(vim.action_for(&key('G')), Some(Action::MoveBottom));
+        assert_eq!(vim.action_for(&ctrl('d')), Some(Action::HalfPageDown));
+        assert_eq!(
+            vim.action_for(&event(KeyCode::Down)),
+            Some(Action::MoveDown)
+        );
+
+        let emacs = Keymap::preset(KeymapPreset::Emacs);
+        assert_eq!(emacs.action_for(&ctrl('n')), Some(Action::MoveDown));
+        assert_eq!(emacs.action_for(&key('j')), None);
+        let alt_lt = KeyEvent::new(KeyCode::Char('<'), KeyModifiers::ALT | KeyModifiers::SHIFT);
+        assert_eq!(emacs.action_for(&alt_lt), Some(Action::MoveTop));
+
+        assert_eq!(KeymapPreset::from_name("vim"), Some(KeymapPreset::Vim));
+        assert_eq!(KeymapPreset::from_name("nano"), None);
+    }
+
+    #[test]
+    fn test_dispatch_sequence() {
+        let keymap = Keymap::preset(KeymapPreset::Vim);
+        let mut dispatcher = KeyDispatcher::new();
+        assert_eq!(dispatcher.feed(&keymap, &key('g')), Dispatch::Pending);
+        assert_eq!(dispatcher.pending_keys(), "g");
+        assert_eq!(
+            dispatcher.feed(&keymap, &key('g')),
+            Dispatch::Action(Action::MoveTop, 1)
+        );
+        assert!(!dispatcher.is_pending());
+
+        // an unbound continuation discards the sequence
+        assert_eq!(dispatcher.feed(&keymap, &key('g')), Dispatch::Pending);
+        assert_eq!(dispatcher.feed(&keymap, &key('x')), Dispatch::Unbound);
+        assert!(!dispatcher.is_pending());
+        assert_eq!(
+            dispatcher.feed(&keymap, &key('j')),
+            Dispatch::Action(Action::MoveDown, 1)
+        );
+    }
+
+    #[test]
+    fn test_dispatch_count() {
+        let keymap = Keymap::preset(KeymapPreset::Vim);
+        let mut dispatcher = KeyDispatcher::new();
+        // a leading zero isn't a count
+        assert_eq!(dispatcher.feed(&keymap, &key('0')), Dispatch::Unbound);
+        assert_eq!(dispatcher.feed(&keymap, &key('1')), Dispatch::Pending);
+        assert_eq!(dispatcher.feed(&keymap, &key('0')), Dispatch::Pending);
+        assert_eq!(dispatcher.pending_keys(), "10");
+        assert_eq!(
+            dispatcher.feed(&keymap, &key('k')),
+            Dispatch::Action(Action::MoveUp, 10)
+        );
+
+        // escape cancels a pending count instead of quitting
+        assert_eq!(dispatcher.feed(&keymap, &key('5')), Dispatch::Pending);
+        assert_eq!(
+            dispatcher.feed(&keymap, &event(KeyCode::Esc)),
+            Dispatch::Unbound
+        );
+        assert_eq!(
+            dispatcher.feed(&keymap, &event(KeyCode::Esc)),
+            Dispatch::Action(Action::Quit, 1)
+        );
+
+        // bound digits aren't counts
+        let mut keymap = Keymap::default();
+        keymap.bind(KeySequence::parse("5").unwrap(), Action::MoveBottom);
+        assert_eq!(
+            dispatcher.feed(&keymap, &key('5')),
+            Dispatch::Action(Action::MoveBottom, 1)
+        );
+    }
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    fn event(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_unbind_action() {
+        let mut keymap = Keymap::default();
+        keymap.unbind_action(Action::Quit);
+        assert!(keymap.keys_for(Action::Quit).is_empty());
+        assert_eq!(keymap.keys_for(Action::MoveUp).len(), 1);
+    }
+}
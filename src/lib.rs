@@ -1,5 +1,18 @@
+pub mod bookmarks;
+pub mod config;
+pub mod conflicts;
+pub mod details;
+pub mod editor;
+pub mod fold;
+pub mod frame;
+pub mod glyphs;
 pub mod graph;
+pub mod journal;
+pub mod keymap;
 pub mod parser;
+pub mod picker;
+pub mod prompt;
 pub mod sapling_cmd;
 pub mod smartlog;
+pub mod style;
 pub mod ui;
@@ -1,26 +1,137 @@
-use std::error::Error;
+use std::{
+    error::Error,
+    io::{self, IsTerminal},
+    iter,
+    path::PathBuf,
+};
 
+use clap::Parser;
 use sl_up::{
-    sapling_cmd::{sl_goto, sl_ssl},
-    smartlog::SmartLog,
-    ui::start_ui_and_get_selected_commit,
+    config::{Config, ConfigError, DefaultAction, Height},
+    details::DetailsCache,
+    prompt::prompt_for_commit,
+    sapling_cmd::{output_text, sl_config, sl_goto, sl_smartlog_stream, GotoMode},
+    smartlog::{SmartLog, SmartlogFeed},
+    ui::start_ui,
 };
 
+/// An interactive TUI smartlog for Sapling SCM.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// Path to the config file [default: $XDG_CONFIG_HOME/sl-up/config.toml]
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Draw in this many rows (e.g. 15) or percent of the terminal (e.g. 40%) below the
+    /// shell's output, instead of full screen
+    #[arg(long, value_name = "HEIGHT")]
+    height: Option<Height>,
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let raw_smartlog_result = sl_ssl();
-    if raw_smartlog_result.is_err() {
-        print!("{}", raw_smartlog_result.unwrap_err());
+    let args = Args::parse();
+    let config = match load_config(&args) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("sl-up: {}", err);
+            std::process::exit(2);
+        }
+    };
+
+    // parse the smartlog on another thread while sl is still printing it
+    let stream = match sl_smartlog_stream(&config.smartlog) {
+        Ok(stream) => stream,
+        Err(err) => {
+            eprintln!("sl-up: can't run sl: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let feed = SmartlogFeed::spawn(stream, |stream| {
+        stream.finish().map_err(|err| err.to_string())
+    });
+    let terminal = io::stdin().is_terminal() && io::stdout().is_terminal();
+    let mut smartlog = SmartLog::from_items([]);
+    let mut done = true;
+    if terminal {
+        // the UI starts once the first screen is parsed, and adds the rest as it comes
+        let (_, rows) = crossterm::terminal::size().unwrap_or((80, 24));
+        while let Some(item) = feed.next_item() {
+            smartlog.extend([item]);
+            if smartlog.has_commits() && smartlog.line_count() >= rows as usize {
+                done = false;
+                break;
+            }
+        }
+    } else {
+        smartlog.extend(iter::from_fn(|| feed.next_item()));
+    }
+    let feed = match done {
+        true => {
+            if let Err(err) = feed.finish() {
+                eprint!("{}", err);
+                std::process::exit(1);
+            }
+            None
+        }
+        false => Some(feed),
+    };
+    if !smartlog.has_commits() {
+        eprintln!("sl-up: the smartlog has no commits to choose from");
         std::process::exit(1);
     }
+    if !terminal {
+        return run_without_terminal(&smartlog, &config);
+    }
 
-    let mut smartlog = SmartLog::new(&raw_smartlog_result.unwrap());
+    if let Some(output) = start_ui(&mut smartlog, feed, &config) {
+        print!("{}", output);
+    }
 
-    let commit_hash = start_ui_and_get_selected_commit(&mut smartlog);
+    Ok(())
+}
 
-    if let Some(commit_hash) = commit_hash {
-        let output = sl_goto(commit_hash).unwrap();
-        print!("{}", String::from_utf8(output.stdout).unwrap());
+/// Prompt for a commit on stderr and run the default action on it, for when there's no terminal
+/// to draw the UI on.
+fn run_without_terminal(smartlog: &SmartLog, config: &Config) -> Result<(), Box<dyn Error>> {
+    let mut details = DetailsCache::new();
+    // without details, commits are listed with the summary shown in the smartlog
+    details.fetch_all(&smartlog.commit_hashes());
+    let hash = match prompt_for_commit(
+        smartlog,
+        &details,
+        &mut io::stdin().lock(),
+        &mut io::stderr(),
+        !io::stdin().is_terminal(),
+    ) {
+        Ok(hash) => hash,
+        Err(err) => {
+            eprintln!("sl-up: {}", err);
+            std::process::exit(1);
+        }
+    };
+    match config.default_action {
+        DefaultAction::Goto => {
+            let output = sl_goto(hash, GotoMode::Check)?;
+            print!("{}", output_text(&output));
+            if !output.status.success() {
+                std::process::exit(output.status.code().unwrap_or(1));
+            }
+        }
+        DefaultAction::Print => println!("{}", hash),
     }
-
     Ok(())
 }
+
+/// Load the config file, then apply the `[sl-up]` section of the repo's Sapling config on top of it,
+/// and the command line options on top of both.
+fn load_config(args: &Args) -> Result<Config, ConfigError> {
+    let mut config = Config::load(args.config.as_deref())?;
+    // outside of a repo this fails, and so will the smartlog with a clearer message
+    if let Ok(entries) = sl_config("sl-up") {
+        config.apply_sapling_config(&entries)?;
+    }
+    if let Some(height) = args.height {
+        config.height = height;
+    }
+    Ok(config)
+}
@@ -1,124 +1,280 @@
+//! Splitting the smartlog output into commits and the glyphs between them.
+//!
+//! Lines are parsed one at a time as they come, so items can be handed out while `sl` is
+//! still printing. A commit is only complete once the next commit starts: until then, graph
+//! lines after it may turn out to be blank lines within its description. Lines are held back
+//! until one gives away the renderer that drew the graph, as its characters tell the graph
+//! from the text, or at least draws with box characters, taken for the default renderer.
+//!
+use std::collections::VecDeque;
+
 use ansi_parser::{AnsiParser, AnsiSequence, Output};
 
-use crate::graph::{Commit, Glyph, Item, ItemType};
+use crate::{
+    glyphs::{GlyphSet, CURVED},
+    graph::{Commit, CommitState, Glyph, Item, ItemType},
+};
 
 const SELECTION_COLOR_CODE: u8 = 35;
 
-pub struct SmartLogParser {}
-impl SmartLogParser {
-    pub fn parse(raw_lines: &[String]) -> Option<Vec<ItemType>> {
-        let mut items: Vec<ItemType> = Vec::new();
-        let mut parsed_lines: Vec<Vec<Output>> =
-            raw_lines.iter().map(|x| x.ansi_parse().collect()).collect();
-
-        while !parsed_lines.is_empty() {
-            let mut line = parsed_lines.remove(0);
-            Self::pre_process_line(&mut line);
-
-            if Self::is_commit_line(&line) {
-                // commit hash and metadata
-                let selected = Self::has_line_selection_coloring(&line);
-                items.push(
-                    Commit::new(vec![Self::parsed_line_to_string_vec(&line)], selected).into(),
-                );
-            } else if Self::parsed_line_to_string(&line).trim().contains(' ') {
-                // commit message
-                items
-                    .last_mut()
-                    .unwrap()
-                    .add_parsed_line(Self::parsed_line_to_string_vec(&line));
-            } else {
-                // only a graph element
-                items.push(Glyph::new(vec![Self::parsed_line_to_string_vec(&line)]).into());
+/// An iterator over the items of the smartlog `lines`, parsing each line once.
+pub struct SmartLogParser<I> {
+    lines: I,
+    /// The renderer that drew the graph, once a line gives it away.
+    glyphs: Option<&'static GlyphSet>,
+    /// Whether the renderer is only guessed to be the curved one, from lines the square one
+    /// would draw the same.
+    guessed: bool,
+    /// Lines read before the renderer is known, parsed once it is.
+    undetected: Vec<String>,
+    /// The commit still being read.
+    commit: Option<Commit>,
+    /// Graph lines read since the commit's last line of text.
+    pending: Vec<Vec<String>>,
+    /// Items complete and waiting to be handed out.
+    ready: VecDeque<ItemType>,
+}
+
+impl<I, S> SmartLogParser<I>
+where
+    I: Iterator<Item = S>,
+    S: AsRef<str>,
+{
+    pub fn new(lines: impl IntoIterator<IntoIter = I>) -> Self {
+        Self {
+            lines: lines.into_iter(),
+            glyphs: None,
+            guessed: false,
+            undetected: Vec::new(),
+            commit: None,
+            pending: Vec::new(),
+            ready: VecDeque::new(),
+        }
+    }
+
+    fn feed(&mut self, raw_line: &str) {
+        let line: Vec<Output> = raw_line.ansi_parse().collect();
+        if self.glyphs.is_none() || self.guessed {
+            let text = plain_text(&line);
+            if let Some(glyphs) = GlyphSet::detect_line(&text) {
+                self.guessed = false;
+                self.detected(glyphs);
+            } else if self.glyphs.is_none() && GlyphSet::is_box_drawn(&text) {
+                // a straight graph may never show a corner: rather than holding every line
+                // back, go on as the default renderer until the square one gives itself away
+                self.guessed = true;
+                self.detected(&CURVED);
             }
         }
-        Some(items)
+        match self.glyphs {
+            Some(glyphs) => self.parse_line(raw_line, line, glyphs),
+            None => self.undetected.push(raw_line.to_string()),
+        }
     }
 
-    pub fn parsed_line_to_string(line: &[Output]) -> String {
-        Self::parsed_line_to_string_vec(line).join("")
+    /// Parse the lines read so far with the `glyphs` of the renderer that drew them.
+    fn detected(&mut self, glyphs: &'static GlyphSet) {
+        self.glyphs = Some(glyphs);
+        for raw_line in std::mem::take(&mut self.undetected) {
+            self.parse_line(&raw_line, raw_line.ansi_parse().collect(), glyphs);
+        }
     }
 
-    pub fn parsed_line_to_string_vec(line: &[Output]) -> Vec<String> {
-        line.iter().map(|x| x.to_string()).collect()
+    /// Parse `line`, the escape codes and text of `raw_line`.
+    fn parse_line(&mut self, raw_line: &str, mut line: Vec<Output>, glyphs: &GlyphSet) {
+        pre_process_line(&mut line, glyphs);
+
+        let text = plain_text(&line);
+        let (graph, text) = glyphs.split_graph(&text);
+        let parsed_line = raw_blocks(raw_line, &line);
+        if let Some(node) = glyphs.node_in(graph) {
+            // commit hash and metadata
+            self.finish_commit();
+            let selected = has_line_selection_coloring(&line);
+            let commit =
+                Commit::new(vec![parsed_line], selected).with_state(CommitState::from_node(node));
+            self.commit = Some(commit);
+        } else if let (false, Some(commit)) = (text.trim().is_empty(), &mut self.commit) {
+            // commit message or annotation, such as "Diff: ..." or a landed marker, after
+            // any blank lines within the message
+            for blank_line in self.pending.drain(..) {
+                commit.add_parsed_line(blank_line);
+            }
+            commit.add_parsed_line(parsed_line);
+        } else if self.commit.is_some() {
+            self.pending.push(parsed_line);
+        } else {
+            // only a graph element
+            self.ready.push_back(Glyph::new(vec![parsed_line]).into());
+        }
     }
 
-    pub fn has_line_selection_coloring(line: &[Output]) -> bool {
-        for block in line.iter() {
-            match block {
-                Output::Escape(AnsiSequence::SetGraphicsMode(codes)) => {
-                    if codes.contains(&SELECTION_COLOR_CODE) {
-                        return true;
-                    }
-                }
-                Output::TextBlock(text) => {
-                    if text.contains("\u{1b}[0;35m") {
-                        return false;
+    /// Hand out the commit being read, and the glyphs after it.
+    fn finish_commit(&mut self) {
+        if let Some(commit) = self.commit.take() {
+            self.ready.push_back(commit.into());
+        }
+        for line in self.pending.drain(..) {
+            self.ready.push_back(Glyph::new(vec![line]).into());
+        }
+    }
+}
+
+impl<I, S> Iterator for SmartLogParser<I>
+where
+    I: Iterator<Item = S>,
+    S: AsRef<str>,
+{
+    type Item = ItemType;
+
+    fn next(&mut self) -> Option<ItemType> {
+        loop {
+            if let Some(item) = self.ready.pop_front() {
+                return Some(item);
+            }
+            match self.lines.next() {
+                Some(line) => self.feed(line.as_ref()),
+                None => {
+                    // nothing gave the renderer away: it's the default one
+                    if !self.undetected.is_empty() {
+                        self.detected(&CURVED);
                     }
+                    self.finish_commit();
+                    return self.ready.pop_front();
                 }
-                _ => {}
             }
         }
-        false
     }
+}
 
-    fn is_commit_line(line: &[Output]) -> bool {
-        let mut first_text_block =
-            Self::get_first_text_block_contents(line).unwrap_or("".to_string());
+impl<'a> SmartLogParser<std::slice::Iter<'a, String>> {
+    /// Parse the whole smartlog at once.
+    pub fn parse(raw_lines: &'a [String]) -> Option<Vec<ItemType>> {
+        Some(Self::new(raw_lines).collect())
+    }
+}
 
-        first_text_block = first_text_block.trim().to_string();
-        if first_text_block.chars().collect::<Vec<char>>().len() == 3
-            && first_text_block.contains(' ')
-        {
-            first_text_block = first_text_block.split(' ').last().unwrap().to_string();
-        }
+pub fn parsed_line_to_string(line: &[Output]) -> String {
+    parsed_line_to_string_vec(line).join("")
+}
+
+pub fn parsed_line_to_string_vec(line: &[Output]) -> Vec<String> {
+    line.iter().map(|x| x.to_string()).collect()
+}
 
-        if ["@", "o"].contains(&first_text_block.as_str()) {
-            return true;
+/// The blocks of `line` as they were written in `raw_line`. Printing the escape codes back
+/// would normalize them, e.g. `\x1b[01m` into `\x1b[1m`, and the output must be Sapling's
+/// exactly.
+fn raw_blocks(raw_line: &str, line: &[Output]) -> Vec<String> {
+    let mut blocks = Vec::with_capacity(line.len());
+    let mut end = 0;
+    for block in line {
+        if let Output::TextBlock(text) = block {
+            // text blocks are slices of the raw line, with the escape codes between them
+            let start = text.as_ptr() as usize - raw_line.as_ptr() as usize;
+            push_escapes(&mut blocks, &raw_line[end..start]);
+            blocks.push(text.to_string());
+            end = start + text.len();
         }
-        false
     }
+    push_escapes(&mut blocks, &raw_line[end..]);
+    blocks
+}
 
-    fn get_first_text_block_contents(line: &[Output]) -> Option<String> {
-        for block in line.iter() {
-            if let Output::TextBlock(text) = block {
-                return Some(text.trim().to_string());
-            }
-        }
-        None
+/// Push each escape code in `escapes` as a block of its own.
+fn push_escapes(blocks: &mut Vec<String>, escapes: &str) {
+    let mut starts: Vec<usize> = escapes
+        .match_indices('\u{1b}')
+        .map(|(idx, _)| idx)
+        .collect();
+    if !escapes.is_empty() && starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+    for (idx, start) in starts.iter().enumerate() {
+        let end = starts.get(idx + 1).copied().unwrap_or(escapes.len());
+        blocks.push(escapes[*start..end].to_string());
     }
+}
 
-    fn pre_process_line(line: &mut Vec<Output>) {
-        if line.len() == 1 {
-            if let Output::TextBlock(text) = &line[0] {
-                let (graph, new_text) = Self::split_graph_from_text(text).unwrap();
-                line[0] = Output::TextBlock(graph);
-                line.push(Output::TextBlock(new_text))
+pub fn has_line_selection_coloring(line: &[Output]) -> bool {
+    for block in line.iter() {
+        match block {
+            Output::Escape(AnsiSequence::SetGraphicsMode(codes))
+                if codes.contains(&SELECTION_COLOR_CODE) =>
+            {
+                return true;
+            }
+            Output::TextBlock(text) if text.contains("\u{1b}[0;35m") => {
+                return false;
             }
+            _ => {}
         }
     }
+    false
+}
 
-    fn split_graph_from_text(text: &str) -> Option<(&str, &str)> {
-        let mut idx = 0;
-        let mut found = false;
-        for (i, char) in text.char_indices() {
-            if found {
-                idx = i;
-                break;
-            }
-            if ["│", "╯", "╷"].contains(&char.to_string().as_str()) {
-                found = true;
-            }
+/// The text of the line, without its escape codes.
+fn plain_text(line: &[Output]) -> String {
+    line.iter()
+        .filter_map(|block| match block {
+            Output::TextBlock(text) => Some(*text),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Split a line without colors into its graph and its text, the way colored lines are.
+fn pre_process_line(line: &mut Vec<Output>, glyphs: &GlyphSet) {
+    if line.len() == 1 {
+        if let Output::TextBlock(text) = &line[0] {
+            let (graph, new_text) = glyphs.split_graph(text);
+            line[0] = Output::TextBlock(graph);
+            line.push(Output::TextBlock(new_text))
         }
-        Some(text.split_at(idx))
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+
+    use proptest::prelude::*;
 
     use super::*;
 
+    /// Lines shaped like the smartlog's, with a graph, hashes, escape codes and other text in
+    /// any order.
+    pub(crate) fn smartlog_lines() -> impl Strategy<Value = Vec<String>> {
+        let escape = prop_oneof![
+            Just("\u{1b}[0m"),
+            Just("\u{1b}[0;35m"),
+            Just("\u{1b}[0;93;1m"),
+            Just("\u{1b}[0;33m"),
+            Just("\u{1b}[0;38;2;141;148;158m"),
+            Just("\u{1b}[m"),
+            Just("\u{1b}[01;32m"),
+        ];
+        let part = prop_oneof![
+            escape.prop_map(String::from),
+            "[0-9a-f]{9}",
+            "[ │╭╮╯╰├┤─╷~|/\\\\:o@x%*]{1,6}",
+            "[a-zA-Z#\\[\\]✓✗ ]{0,12}",
+            "\\PC{0,4}",
+        ];
+        let line = prop::collection::vec(part, 0..8).prop_map(|parts| parts.concat());
+        prop::collection::vec(line, 0..24)
+    }
+
+    proptest! {
+        #[test]
+        fn parse_keeps_every_line(lines in smartlog_lines()) {
+            let parsed: Vec<String> = SmartLogParser::parse(&lines).unwrap()
+                .iter()
+                .flat_map(|item| item.to_string_vec())
+                .collect();
+            prop_assert_eq!(parsed, lines);
+        }
+    }
+
     const RAW_LINES: [&str; 15] = [
         "  @  \u{1b}[0;35m\u{1b}[0;93;1m1cee5d55e\u{1b}[0m\u{1b}[0;35m  Dec 08 at 09:46  royrothenberg  \u{1b}[0;36m#780 Closed\u{1b}[0m\u{1b}[0;35m \u{1b}[0;31m✗\u{1b}[0m",
         "  │  \u{1b}[0;35m[pr body update] update stack list without overwriting PR title and body\u{1b}[0m",
@@ -151,7 +307,237 @@ mod tests {
         assert!(commit.selected);
     }
 
+    /// The same graph as [`RAW_LINES`], drawn with square corners.
+    const SQUARE_LINES: [&str; 15] = [
+        "  @  \u{1b}[0;35m\u{1b}[0;93;1m1cee5d55e\u{1b}[0m\u{1b}[0;35m  Dec 08 at 09:46  royrothenberg  \u{1b}[0;36m#780 Closed\u{1b}[0m\u{1b}[0;35m \u{1b}[0;31m✗\u{1b}[0m",
+        "  │  \u{1b}[0;35m[pr body update] update stack list without overwriting PR title and body\u{1b}[0m",
+        "  │",
+        "  o  \u{1b}[0;93;1mc3bd9e5fa\u{1b}[0m  Dec 08 at 09:46  royrothenberg  \u{1b}[0;38;2;141;148;158m#779 Unreviewed\u{1b}[0m \u{1b}[0;31m✗\u{1b}[0m",
+        "┌─┘  [pr body update] fix reviewstack option breaking stack list detection",
+        "│",
+        "o  \u{1b}[0;33mba27d4d13\u{1b}[0m  Dec 07 at 22:20  \u{1b}[0;32mremote/main\u{1b}[0m",
+        "╷",
+        "╷ o  \u{1b}[0;93;1m2f85065e7\u{1b}[0m  Nov 28 at 11:49  royrothenberg  \u{1b}[0;36m#781 Closed\u{1b}[0m \u{1b}[0;32m✓\u{1b}[0m",
+        "┌─┘  [isl] increase width of diff window in split stack edit panel",
+        "│",
+        "o  \u{1b}[0;33m0e069ab09\u{1b}[0m  Nov 21 at 13:16",
+        "│",
+        "~",
+        "",
+    ];
+
+    /// The same graph as [`RAW_LINES`], drawn by the ASCII renderer, with an obsolete commit.
+    const ASCII_LINES: [&str; 18] = [
+        "  @  \u{1b}[0;35m\u{1b}[0;93;1m1cee5d55e\u{1b}[0m\u{1b}[0;35m  Dec 08 at 09:46  royrothenberg  \u{1b}[0;36m#780 Closed\u{1b}[0m\u{1b}[0;35m \u{1b}[0;31m✗\u{1b}[0m",
+        "  |  \u{1b}[0;35m[pr body update] update stack list without overwriting PR title and body\u{1b}[0m",
+        "  |",
+        "  o  \u{1b}[0;93;1mc3bd9e5fa\u{1b}[0m  Dec 08 at 09:46  royrothenberg  \u{1b}[0;38;2;141;148;158m#779 Unreviewed\u{1b}[0m \u{1b}[0;31m✗\u{1b}[0m",
+        " /   [pr body update] fix reviewstack option breaking stack list detection",
+        "|",
+        "o  \u{1b}[0;33mba27d4d13\u{1b}[0m  Dec 07 at 22:20  \u{1b}[0;32mremote/main\u{1b}[0m",
+        ":",
+        ": x  \u{1b}[0;93;1m5d1f0e3a2\u{1b}[0m  Nov 29 at 10:02  royrothenberg",
+        ":/   x marks the spot",
+        ":",
+        ": o  \u{1b}[0;93;1m2f85065e7\u{1b}[0m  Nov 28 at 11:49  royrothenberg  \u{1b}[0;36m#781 Closed\u{1b}[0m \u{1b}[0;32m✓\u{1b}[0m",
+        ":/   [isl] increase width of diff window in split stack edit panel",
+        "|",
+        "o  \u{1b}[0;33m0e069ab09\u{1b}[0m  Nov 21 at 13:16",
+        "|",
+        "~",
+        "",
+    ];
+
+    #[test]
+    fn square_graph_items() {
+        let items = SmartLogParser::parse(&to_lines(&SQUARE_LINES)).unwrap();
+        assert_eq!(items.len(), 12);
+        assert_eq!(
+            commit_hashes(&items),
+            [
+                "1cee5d55e",
+                "c3bd9e5fa",
+                "ba27d4d13",
+                "2f85065e7",
+                "0e069ab09"
+            ]
+        );
+        assert_eq!(items[2].parsed_lines().len(), 2);
+    }
+
+    #[test]
+    fn ascii_graph_items() {
+        let items = SmartLogParser::parse(&to_lines(&ASCII_LINES)).unwrap();
+        assert_eq!(items.len(), 14);
+        assert_eq!(
+            commit_hashes(&items),
+            [
+                "1cee5d55e",
+                "c3bd9e5fa",
+                "ba27d4d13",
+                "5d1f0e3a2",
+                "2f85065e7",
+                "0e069ab09"
+            ]
+        );
+        // the description starting with "x" is the obsolete commit's, not another commit
+        assert_eq!(items[6].parsed_lines().len(), 2);
+        assert_eq!(items[2].parsed_lines().len(), 2);
+    }
+
+    #[test]
+    fn detects_glyphs_past_the_first_lines() {
+        // the graph is colored, and the first line has no node nor any ASCII-only character
+        let lines = [
+            "\u{1b}[0;35m:\u{1b}[0m",
+            "\u{1b}[0;35m| o\u{1b}[0m  \u{1b}[0;93;1m2f85065e7\u{1b}[0m  Nov 28 at 11:49  royrothenberg",
+            "\u{1b}[0;35m|/\u{1b}[0m   [isl] increase width of diff window in split stack edit panel",
+            "o  \u{1b}[0;33m0e069ab09\u{1b}[0m  Nov 21 at 13:16",
+            "~",
+        ];
+        let items = SmartLogParser::parse(&to_lines(&lines)).unwrap();
+        assert_eq!(commit_hashes(&items), ["2f85065e7", "0e069ab09"]);
+        assert_eq!(item_shapes(&lines), [-1, 2, 1, -1]);
+    }
+
+    #[test]
+    fn commit_states() {
+        let items = SmartLogParser::parse(&to_lines(&ASCII_LINES)).unwrap();
+        let states: Vec<CommitState> = items
+            .iter()
+            .filter_map(|item| match item {
+                ItemType::Commit(commit) => Some(commit.state),
+                _ => None,
+            })
+            .collect();
+        use CommitState::*;
+        assert_eq!(
+            states,
+            [WorkingCopy, Normal, Normal, Obsolete, Normal, Normal]
+        );
+
+        let lines = to_lines(&["%  \u{1b}[0;93;1m5d1f0e3a2\u{1b}[0m  Nov 29 at 10:02", "│"]);
+        let items = SmartLogParser::parse(&lines).unwrap();
+        let ItemType::Commit(commit) = &items[0] else {
+            panic!("Expected GraphCommit");
+        };
+        assert_eq!(commit.state, Conflicted);
+    }
+
+    #[test]
+    fn streams_items() {
+        let read = std::cell::Cell::new(0);
+        let lines = RAW_LINES.iter().inspect(|_| read.set(read.get() + 1));
+        let mut parser = SmartLogParser::new(lines);
+        // the first commit is complete once the next one starts
+        assert!(matches!(parser.next(), Some(ItemType::Commit(_))));
+        assert_eq!(read.get(), 4);
+        assert!(matches!(parser.next(), Some(ItemType::Glyph(_))));
+        assert_eq!(parser.count(), 10);
+    }
+
+    #[test]
+    fn streams_graphs_without_corners() {
+        let lines = [
+            "@  \u{1b}[0;93;1m1cee5d55e\u{1b}[0m  Dec 08 at 09:46  royrothenberg",
+            "│  update stack list",
+            "│",
+            "o  \u{1b}[0;33mba27d4d13\u{1b}[0m  Dec 07 at 22:20",
+            "│  fix reviewstack option",
+            "~",
+        ];
+        let read = std::cell::Cell::new(0);
+        let mut parser = SmartLogParser::new(lines.iter().inspect(|_| read.set(read.get() + 1)));
+        // nothing tells the curved renderer from the square one, which draw these lines alike
+        assert!(matches!(parser.next(), Some(ItemType::Commit(_))));
+        assert_eq!(read.get(), 4);
+        assert_eq!(item_shapes(&lines), [2, -1, 2, -1]);
+    }
+
+    /// The number of lines of each item, negative for glyphs.
+    fn item_shapes(lines: &[&str]) -> Vec<isize> {
+        SmartLogParser::parse(&to_lines(lines))
+            .unwrap()
+            .iter()
+            .map(|item| match item {
+                ItemType::Commit(commit) => commit.parsed_lines().len() as isize,
+                ItemType::Glyph(glyph) => -(glyph.parsed_lines().len() as isize),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_word_description() {
+        let lines = [
+            "o  \u{1b}[0;93;1m2f85065e7\u{1b}[0m  Nov 28 at 11:49",
+            "│  wip",
+            "│",
+            "o  \u{1b}[0;33m0e069ab09\u{1b}[0m  Nov 21 at 13:16",
+            "   wip",
+        ];
+        assert_eq!(item_shapes(&lines), [2, -1, 2]);
+    }
+
+    #[test]
+    fn empty_description() {
+        let lines = [
+            "o  \u{1b}[0;93;1m2f85065e7\u{1b}[0m  Nov 28 at 11:49",
+            "│",
+            "o  \u{1b}[0;33m0e069ab09\u{1b}[0m  Nov 21 at 13:16",
+            "~",
+        ];
+        assert_eq!(item_shapes(&lines), [1, -1, 1, -1]);
+    }
+
+    #[test]
+    fn multi_line_description() {
+        let lines = [
+            "  o  \u{1b}[0;93;1m2f85065e7\u{1b}[0m  Nov 28 at 11:49",
+            "  │  [isl] increase width of diff window",
+            "  │",
+            "  │  in split stack edit panel",
+            "  │",
+            "  o  \u{1b}[0;93;1m0e069ab09\u{1b}[0m  Nov 21 at 13:16",
+            "╭─╯",
+        ];
+        assert_eq!(item_shapes(&lines), [4, -1, 1, -1]);
+    }
+
+    #[test]
+    fn annotation_lines() {
+        let lines = [
+            "o  \u{1b}[0;93;1m2f85065e7\u{1b}[0m  Nov 28 at 11:49",
+            "│  [isl] increase width of diff window",
+            "│  Diff: D51234 (Landed)",
+            "│  \u{1b}[0;32mLanded as 9a8b7c6d5\u{1b}[0m",
+            "│ │",
+            "│ o  \u{1b}[0;93;1m0e069ab09\u{1b}[0m  Nov 21 at 13:16",
+            "├─╯  wip",
+        ];
+        assert_eq!(item_shapes(&lines), [4, -1, 2]);
+    }
+
+    #[test]
+    fn text_before_any_commit() {
+        let lines = ["some warning from sl", "o  \u{1b}[0;33m0e069ab09\u{1b}[0m"];
+        assert_eq!(item_shapes(&lines), [-1, 1]);
+    }
+
+    fn commit_hashes(items: &[ItemType]) -> Vec<&str> {
+        items
+            .iter()
+            .filter_map(|item| match item {
+                ItemType::Commit(commit) => commit.hash(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn to_lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|x| x.to_string()).collect()
+    }
+
     fn raw_lines() -> Vec<String> {
-        RAW_LINES.iter().map(|x| x.to_string()).collect()
+        to_lines(&RAW_LINES)
     }
 }
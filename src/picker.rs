@@ -0,0 +1,234 @@
+//! A list filtered by fuzzy matching as the user types, used to pick a bookmark or a commit.
+//!
+
+/// Score how well `query` matches `candidate`: its characters must all appear in order,
+/// ignoring case. Consecutive characters and ones at the start of a word score higher.
+/// Returns `None` if it doesn't match at all.
+///
+/// ```
+/// # use sl_up::picker::fuzzy_score;
+/// assert!(fuzzy_score("rm", "remote/main").is_some());
+/// assert!(fuzzy_score("mr", "remote/main").is_none());
+/// assert!(fuzzy_score("main", "remote/main") > fuzzy_score("main", "my-amazing-branch"));
+/// ```
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    fuzzy_match(query, candidate).map(|(score, _)| score)
+}
+
+/// The positions, in characters, of the best match of `query` in `candidate`, e.g. to
+/// highlight them.
+///
+/// ```
+/// # use sl_up::picker::fuzzy_positions;
+/// assert_eq!(fuzzy_positions("main", "remote/main"), Some(vec![7, 8, 9, 10]));
+/// assert_eq!(fuzzy_positions("mr", "remote/main"), None);
+/// ```
+pub fn fuzzy_positions(query: &str, candidate: &str) -> Option<Vec<usize>> {
+    fuzzy_match(query, candidate).map(|(_, positions)| positions)
+}
+
+/// The score of the best match of `query` in `candidate`, and its positions.
+fn fuzzy_match(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let candidate: Vec<char> = candidate.chars().collect();
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let matches = |q: char, c: char| c.to_lowercase().eq(q.to_lowercase());
+    let bonus = |idx: usize| -> i64 {
+        if idx == 0 || !candidate[idx - 1].is_alphanumeric() {
+            4
+        } else {
+            1
+        }
+    };
+    // gaps cost a little, so tighter matches come first
+    let gap = |len: usize| len.min(10) as i64;
+
+    // best[j]: the best score with the current query character matched at candidate[j]
+    let mut best: Vec<Option<i64>> = candidate
+        .iter()
+        .enumerate()
+        .map(|(j, c)| matches(query[0], *c).then(|| bonus(j)))
+        .collect();
+    // previous[i][j]: where the query character before the i-th was matched, for its best
+    // score at candidate[j]
+    let mut previous: Vec<Vec<usize>> = Vec::with_capacity(query.len());
+    for q in &query[1..] {
+        let mut next = vec![None; candidate.len()];
+        let mut links = vec![0; candidate.len()];
+        for (j, c) in candidate.iter().enumerate() {
+            if !matches(*q, *c) {
+                continue;
+            }
+            let link = (0..j)
+                .filter_map(|k| {
+                    let previous = best[k]?;
+                    let link = if k + 1 == j { 5 } else { -gap(j - k - 1) };
+                    Some((previous + link, k))
+                })
+                // the first of equal scores, like the matches found from the left
+                .max_by_key(|(score, k)| (*score, std::cmp::Reverse(*k)));
+            if let Some((score, k)) = link {
+                next[j] = Some(score + bonus(j));
+                links[j] = k;
+            }
+        }
+        best = next;
+        previous.push(links);
+    }
+    let (score, mut j) = best
+        .into_iter()
+        .enumerate()
+        .filter_map(|(j, score)| Some((score?, j)))
+        .max_by_key(|(score, j)| (*score, std::cmp::Reverse(*j)))?;
+    let mut positions = vec![j];
+    for links in previous.iter().rev() {
+        j = links[j];
+        positions.push(j);
+    }
+    positions.reverse();
+    Some((score, positions))
+}
+
+/// The items matching the query, best first, and the one under the cursor.
+#[derive(Debug)]
+pub struct Picker {
+    items: Vec<String>,
+    query: String,
+    /// Indices into `items`.
+    matches: Vec<usize>,
+    cursor: usize,
+}
+impl Picker {
+    pub fn new(items: Vec<String>) -> Self {
+        let mut picker = Self {
+            items,
+            query: String::new(),
+            matches: Vec::new(),
+            cursor: 0,
+        };
+        picker.filter();
+        picker
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.filter();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.filter();
+    }
+
+    fn filter(&mut self) {
+        let mut scored: Vec<(i64, usize, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, item)| {
+                let score = fuzzy_score(&self.query, item)?;
+                let len = if self.query.is_empty() {
+                    0
+                } else {
+                    item.chars().count()
+                };
+                Some((score, len, idx))
+            })
+            .collect();
+        // best score first, then the shortest, then the original order (all of it without a query)
+        scored.sort_by_key(|(score, len, idx)| (-score, *len, *idx));
+        self.matches = scored.into_iter().map(|(_, _, idx)| idx).collect();
+        self.cursor = 0;
+    }
+
+    /// The matching items, best first.
+    pub fn matches(&self) -> impl Iterator<Item = &str> + '_ {
+        self.matches.iter().map(|idx| self.items[*idx].as_str())
+    }
+
+    pub fn match_count(&self) -> usize {
+        self.matches.len()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn move_up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        if self.cursor + 1 < self.matches.len() {
+            self.cursor += 1;
+        }
+    }
+
+    /// The index, in the original items, of the match under the cursor.
+    pub fn selected(&self) -> Option<usize> {
+        self.matches.get(self.cursor).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert!(fuzzy_score("FEAT", "feature").is_some());
+        assert!(fuzzy_score("xyz", "feature").is_none());
+        // consecutive beats scattered
+        assert!(fuzzy_score("fea", "feature") > fuzzy_score("fea", "f-e-a"));
+        // word starts beat the middle of words
+        assert!(fuzzy_score("m", "remote/main") > fuzzy_score("m", "remote"));
+    }
+
+    #[test]
+    fn test_fuzzy_positions() {
+        assert_eq!(fuzzy_positions("", "main"), Some(vec![]));
+        assert_eq!(
+            fuzzy_positions("main", "remote/main"),
+            Some(vec![7, 8, 9, 10])
+        );
+        // the start of a word rather than the first match
+        assert_eq!(fuzzy_positions("wm", "my-work/main"), Some(vec![3, 8]));
+        assert_eq!(fuzzy_positions("FEA", "feature"), Some(vec![0, 1, 2]));
+        assert_eq!(fuzzy_positions("xyz", "feature"), None);
+    }
+
+    #[test]
+    fn test_picker() {
+        let items = vec![
+            "feature".to_string(),
+            "remote/main".to_string(),
+            "main".to_string(),
+        ];
+        let mut picker = Picker::new(items);
+        assert_eq!(picker.match_count(), 3);
+        assert_eq!(picker.selected(), Some(0));
+
+        for c in "main".chars() {
+            picker.push(c);
+        }
+        let matches: Vec<&str> = picker.matches().collect();
+        assert_eq!(matches, vec!["main", "remote/main"]);
+        picker.move_down();
+        picker.move_down();
+        assert_eq!(picker.selected(), Some(1));
+
+        picker.push('x');
+        assert_eq!(picker.match_count(), 0);
+        assert_eq!(picker.selected(), None);
+        picker.pop();
+        assert_eq!(picker.query(), "main");
+        assert_eq!(picker.cursor(), 0);
+    }
+}
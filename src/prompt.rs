@@ -0,0 +1,284 @@
+//! Choosing a commit without the interactive UI, when stdin or stdout isn't a terminal, e.g. in
+//! an editor integration or a CI log.
+//!
+//! The commits are listed with a number, and a number or a hash is read from stdin. The list
+//! and the prompt go to stderr, leaving stdout to the result, such as the hash printed by the
+//! `print` action.
+//!
+use std::{
+    error::Error,
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    details::DetailsCache,
+    graph::{Commit, CommitState, Item, ItemType},
+    smartlog::SmartLog,
+};
+
+#[derive(Debug)]
+pub enum PromptError {
+    /// Stdin ended before a commit was chosen.
+    NoAnswer,
+    Io(io::Error),
+}
+impl fmt::Display for PromptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromptError::NoAnswer => write!(
+                f,
+                "no commit chosen: not running in a terminal, and stdin ended without a commit \
+                 number or hash"
+            ),
+            PromptError::Io(err) => write!(f, "can't prompt for a commit: {}", err),
+        }
+    }
+}
+impl Error for PromptError {}
+impl From<io::Error> for PromptError {
+    fn from(err: io::Error) -> Self {
+        PromptError::Io(err)
+    }
+}
+
+/// List the commits of `smartlog` to `output`, then read a commit number or hash from `input`
+/// until one matches. Answers are written after the prompt if `echo`, for input that isn't
+/// typed on a terminal. Returns the chosen commit's hash.
+pub fn prompt_for_commit<'a>(
+    smartlog: &'a SmartLog,
+    details: &DetailsCache,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    echo: bool,
+) -> Result<&'a str, PromptError> {
+    for line in commit_list(smartlog, details) {
+        writeln!(output, "{}", line)?;
+    }
+    let hashes = smartlog.commit_hashes();
+    let default = smartlog.get_selected_commit_hash();
+    loop {
+        match default {
+            Some(hash) => write!(output, "Commit number or hash [{}]: ", hash)?,
+            None => write!(output, "Commit number or hash: ")?,
+        }
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            // end the prompt's line
+            writeln!(output)?;
+            return Err(PromptError::NoAnswer);
+        }
+        let answer = answer.trim();
+        if echo {
+            writeln!(output, "{}", answer)?;
+        }
+        if let (true, Some(hash)) = (answer.is_empty(), default) {
+            return Ok(hash);
+        }
+        match choose(&hashes, answer) {
+            Ok(hash) => return Ok(hash),
+            Err(err) => writeln!(output, "{}", err)?,
+        }
+    }
+}
+
+/// The commits of `smartlog` from top to bottom, one per line with their number, hash, author
+/// and summary, e.g. `  2  c3bd9e5fa  roy@example.com  fix reviewstack option`.
+/// The working copy is marked with `@`, and obsolete and conflicted commits are flagged.
+pub fn commit_list(smartlog: &SmartLog, details: &DetailsCache) -> Vec<String> {
+    let commits: Vec<&Commit> = smartlog
+        .items()
+        .iter()
+        .filter_map(|item| match item {
+            ItemType::Commit(commit) if commit.hash().is_some() => Some(commit),
+            _ => None,
+        })
+        .collect();
+    let width = commits.len().to_string().len();
+    commits
+        .iter()
+        .enumerate()
+        .map(|(idx, commit)| {
+            let hash = commit.hash().unwrap_or_default();
+            let (author, summary) = match details.get(hash) {
+                Some(details) => (
+                    details.author_email.as_str(),
+                    details.description.lines().next().unwrap_or_default(),
+                ),
+                None => ("", ""),
+            };
+            let summary = match summary {
+                "" => smartlog_summary(commit),
+                summary => summary.to_string(),
+            };
+            let node = match commit.state {
+                CommitState::WorkingCopy => '@',
+                _ => ' ',
+            };
+            let mut line = format!("{} {:>width$}  {}", node, idx + 1, hash, width = width);
+            for field in [author, summary.as_str()] {
+                if !field.is_empty() {
+                    line.push_str("  ");
+                    line.push_str(field);
+                }
+            }
+            match commit.state {
+                CommitState::Obsolete => line.push_str("  (obsolete)"),
+                CommitState::Conflicted => line.push_str("  (conflicted)"),
+                CommitState::Normal | CommitState::WorkingCopy => {}
+            }
+            line
+        })
+        .collect()
+}
+
+/// The first line of a commit's description as the smartlog shows it, on the commit's second
+/// line, for when its details couldn't be fetched.
+fn smartlog_summary(commit: &Commit) -> String {
+    let Some(line) = commit.parsed_lines().get(1) else {
+        return String::new();
+    };
+    // the first block is the graph
+    let text: String = line
+        .iter()
+        .skip(1)
+        .filter(|text| !text.starts_with('\u{1b}'))
+        .map(String::as_str)
+        .collect();
+    text.trim().to_string()
+}
+
+/// The hash chosen by `answer`: a commit's number in the list, counted from 1, or the start of
+/// its hash.
+///
+/// ```
+/// # use sl_up::prompt::choose;
+/// let hashes = ["1cee5d55e", "c3bd9e5fa", "c3a0b1d2e"];
+/// assert_eq!(choose(&hashes, "2"), Ok("c3bd9e5fa"));
+/// assert_eq!(choose(&hashes, "c3b"), Ok("c3bd9e5fa"));
+/// assert!(choose(&hashes, "c3").is_err());
+/// ```
+pub fn choose<'a>(hashes: &[&'a str], answer: &str) -> Result<&'a str, String> {
+    if let Ok(number) = answer.parse::<usize>() {
+        if (1..=hashes.len()).contains(&number) {
+            return Ok(hashes[number - 1]);
+        }
+    }
+    let prefix = answer.to_lowercase();
+    let matches: Vec<&str> = hashes
+        .iter()
+        .copied()
+        // a full hash is longer than the short one in the smartlog
+        .filter(|hash| {
+            !prefix.is_empty() && (hash.starts_with(&prefix) || prefix.starts_with(hash))
+        })
+        .collect();
+    match matches.as_slice() {
+        [hash] => Ok(hash),
+        [] => Err(format!(
+            "'{}' is neither a number from 1 to {} nor a commit's hash",
+            answer,
+            hashes.len()
+        )),
+        _ => Err(format!(
+            "'{}' matches {} commits: {}",
+            answer,
+            matches.len(),
+            matches.join(", ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW_LINES: [&str; 9] = [
+        "  @  \u{1b}[0;35m\u{1b}[0;93;1m1cee5d55e\u{1b}[0m\u{1b}[0;35m  Dec 08 at 09:46  royrothenberg\u{1b}[0m",
+        "  │  \u{1b}[0;35m[pr body update] update stack list\u{1b}[0m",
+        "  │",
+        "  x  \u{1b}[0;93;1mc3bd9e5fa\u{1b}[0m  Dec 08 at 09:46  royrothenberg",
+        "╭─╯  [pr body update] fix reviewstack option",
+        "│",
+        "o  \u{1b}[0;33mba27d4d13\u{1b}[0m  Dec 07 at 22:20  \u{1b}[0;32mremote/main\u{1b}[0m",
+        "~",
+        "",
+    ];
+
+    fn smartlog() -> SmartLog {
+        let lines: Vec<String> = RAW_LINES.iter().map(|x| x.to_string()).collect();
+        SmartLog::new(&lines)
+    }
+
+    fn prompt(smartlog: &SmartLog, input: &str) -> (Result<String, String>, String) {
+        let mut output = Vec::new();
+        let hash = prompt_for_commit(
+            smartlog,
+            &DetailsCache::new(),
+            &mut input.as_bytes(),
+            &mut output,
+            true,
+        )
+        .map(String::from)
+        .map_err(|err| err.to_string());
+        (hash, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_commit_list() {
+        assert_eq!(
+            commit_list(&smartlog(), &DetailsCache::new()),
+            vec![
+                "@ 1  1cee5d55e  [pr body update] update stack list",
+                "  2  c3bd9e5fa  [pr body update] fix reviewstack option  (obsolete)",
+                "  3  ba27d4d13",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prompt() {
+        let smartlog = smartlog();
+        let (hash, output) = prompt(&smartlog, "2\n");
+        assert_eq!(hash, Ok("c3bd9e5fa".to_string()));
+        assert!(output.starts_with("@ 1  1cee5d55e"));
+        assert!(output.ends_with("Commit number or hash [1cee5d55e]: 2\n"));
+
+        // the working copy by default
+        assert_eq!(prompt(&smartlog, "\n").0, Ok("1cee5d55e".to_string()));
+        // asked again until an answer matches
+        let (hash, output) = prompt(&smartlog, "7\nBA27D4D13aaaa\n");
+        assert_eq!(hash, Ok("ba27d4d13".to_string()));
+        assert!(output.contains("'7' is neither a number from 1 to 3 nor a commit's hash"));
+    }
+
+    #[test]
+    fn test_no_answer() {
+        let (hash, output) = prompt(&smartlog(), "");
+        assert!(hash.unwrap_err().contains("no commit chosen"));
+        assert!(output.ends_with(": \n"));
+        let (hash, _) = prompt(&smartlog(), "zzz\n");
+        assert!(hash.is_err());
+    }
+
+    #[test]
+    fn test_choose() {
+        let hashes = ["1cee5d55e", "c3bd9e5fa", "c3a0b1d2e"];
+        assert_eq!(choose(&hashes, "1"), Ok("1cee5d55e"));
+        assert_eq!(choose(&hashes, "3"), Ok("c3a0b1d2e"));
+        // not a number in the list, but the start of a hash
+        assert_eq!(choose(&hashes, "1c"), Ok("1cee5d55e"));
+        assert_eq!(choose(&hashes, "C3A0"), Ok("c3a0b1d2e"));
+        assert_eq!(
+            choose(&hashes, "c3bd9e5fa0123456789abcdef0123456789abcde"),
+            Ok("c3bd9e5fa")
+        );
+        assert!(choose(&hashes, "4").unwrap_err().contains("from 1 to 3"));
+        assert!(choose(&hashes, "c3")
+            .unwrap_err()
+            .contains("matches 2 commits"));
+        assert!(choose(&hashes, "").is_err());
+        assert!(choose(&[], "1").is_err());
+    }
+}
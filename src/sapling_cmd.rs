@@ -1,21 +1,441 @@
-use std::process::Command;
+use std::{
+    io::{BufRead, BufReader, Read, Split},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Stdio},
+    thread::{self, JoinHandle},
+};
 
-pub fn sl_ssl() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+use crate::{
+    bookmarks::{self, Bookmark},
+    config::SmartlogSettings,
+    conflicts::{ConflictedFile, UnfinishedOperation},
+    journal::{self, JournalEntry},
+};
+
+/// Ask for colors unless `NO_COLOR` is set (see <https://no-color.org>); the parser finds its
+/// way through plain output too.
+fn color_flag() -> &'static str {
+    if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        "--color=never"
+    } else {
+        "--color=always"
+    }
+}
+
+pub fn sl_smartlog(settings: &SmartlogSettings) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut stream = sl_smartlog_stream(settings)?;
+    let lines = stream.by_ref().collect();
+    stream.finish()?;
+    Ok(lines)
+}
+
+/// Start `sl` printing the smartlog, to read its lines as they come.
+pub fn sl_smartlog_stream(settings: &SmartlogSettings) -> std::io::Result<SmartlogStream> {
+    let mut args = settings.command.clone();
+    if let Some(revset) = &settings.revset {
+        args.extend(["--rev".to_string(), revset.clone()]);
+    }
+    args.push(color_flag().to_string());
+    let mut child = Command::new("sl")
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().unwrap();
+    // read stderr as it comes too: if its pipe filled up, sl would stop printing the smartlog
+    let mut stderr = child.stderr.take().unwrap();
+    let stderr = thread::spawn(move || {
+        let mut text = Vec::new();
+        let _ = stderr.read_to_end(&mut text);
+        text
+    });
+    Ok(SmartlogStream {
+        child,
+        lines: BufReader::new(stdout).split(b'\n'),
+        stderr,
+    })
+}
+
+/// The lines of the smartlog as `sl` prints them. Check how it went with [`Self::finish`]
+/// once they're all read.
+pub struct SmartlogStream {
+    child: Child,
+    lines: Split<BufReader<ChildStdout>>,
+    /// What `sl` printed on stderr, read on its own thread.
+    stderr: JoinHandle<Vec<u8>>,
+}
+impl Iterator for SmartlogStream {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let line = self.lines.next()?.ok()?;
+        Some(String::from_utf8_lossy(&line).into_owned())
+    }
+}
+impl SmartlogStream {
+    /// Wait for `sl` to exit, returning what it printed on stderr if it failed.
+    pub fn finish(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // stop reading the smartlog, in case it wasn't read to the end
+        drop(self.lines);
+        let status = self.child.wait()?;
+        let stderr = self.stderr.join().unwrap_or_default();
+        if !status.success() {
+            return Err(String::from_utf8_lossy(&stderr).into_owned().into());
+        }
+        Ok(())
+    }
+}
+
+/// How `sl goto` treats uncommitted changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GotoMode {
+    /// Refuse to update over conflicting uncommitted changes.
+    Check,
+    /// Merge uncommitted changes into the destination (`--merge`).
+    Merge,
+    /// Discard uncommitted changes (`--clean`).
+    Clean,
+}
+
+pub fn sl_goto(hash: &str, mode: GotoMode) -> Result<std::process::Output, std::io::Error> {
+    let mut args = vec!["goto", hash];
+    match mode {
+        GotoMode::Check => {}
+        GotoMode::Merge => args.push("--merge"),
+        GotoMode::Clean => args.push("--clean"),
+    }
+    Command::new("sl").args(args).output()
+}
+
+/// A move of the working copy along its stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackMove {
+    /// `sl next n`: n commits up the stack.
+    Next(usize),
+    /// `sl prev n`: n commits down the stack.
+    Prev(usize),
+    /// `sl next --top`: the top of the stack.
+    Top,
+    /// `sl prev --bottom`: the bottom of the stack.
+    Bottom,
+}
+impl StackMove {
+    /// Whether this can stop at a commit with several children, where `--towards` picks one.
+    pub fn goes_up(&self) -> bool {
+        matches!(self, StackMove::Next(_) | StackMove::Top)
+    }
+
+    fn args(&self) -> Vec<String> {
+        match self {
+            StackMove::Next(count) => vec!["next".to_string(), count.to_string()],
+            StackMove::Prev(count) => vec!["prev".to_string(), count.to_string()],
+            StackMove::Top => vec!["next".to_string(), "--top".to_string()],
+            StackMove::Bottom => vec!["prev".to_string(), "--bottom".to_string()],
+        }
+    }
+}
+
+/// Move the working copy along its stack. Going up, `towards` picks the way at a fork.
+pub fn sl_stack_move(
+    stack_move: StackMove,
+    towards: Option<&str>,
+) -> Result<std::process::Output, std::io::Error> {
+    let mut args = stack_move.args();
+    if let Some(towards) = towards.filter(|_| stack_move.goes_up()) {
+        args.extend(["--towards".to_string(), towards.to_string()]);
+    }
+    Command::new("sl").args(args).output()
+}
+
+/// Whether `sl next` failed because the working copy's stack forks above it.
+pub fn is_ambiguous_next(output: &str) -> bool {
+    let output = output.to_lowercase();
+    output.contains("ambiguous") || output.contains("multiple children")
+}
+
+/// The draft heads above the working copy, as `(short hash, first line of description)`:
+/// the commits `sl next --towards` can head to when the stack forks.
+pub fn sl_next_heads() -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let output = sl_log(
+        "heads((.:: & draft()) - .)",
+        "{node|short}\u{1f}{desc|firstline}\u{1e}",
+    )?;
+    Ok(parse_heads(&output))
+}
+
+fn parse_heads(output: &str) -> Vec<(String, String)> {
+    output
+        .split('\u{1e}')
+        .filter_map(|record| record.trim_start_matches('\n').split_once('\u{1f}'))
+        .map(|(hash, title)| (hash.to_string(), title.to_string()))
+        .collect()
+}
+
+/// The uncommitted changes to tracked files, as printed by `sl status` (e.g. `M src/main.rs`).
+/// Untracked files don't prevent a goto, so they are left out.
+pub fn sl_status() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let output = Command::new("sl")
+        .args(vec![
+            "status",
+            "--modified",
+            "--added",
+            "--removed",
+            "--deleted",
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Err(output_text(&output).into());
+    }
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .map(String::from)
+        .collect())
+}
+
+pub fn sl_shelve() -> Result<std::process::Output, std::io::Error> {
+    Command::new("sl").args(vec!["shelve"]).output()
+}
+
+pub fn sl_unshelve() -> Result<std::process::Output, std::io::Error> {
+    Command::new("sl").args(vec!["unshelve"]).output()
+}
+
+/// The files with merge conflicts, resolved or not. Empty when no merge is in progress.
+pub fn sl_resolve_list() -> Result<Vec<ConflictedFile>, Box<dyn std::error::Error>> {
+    let output = Command::new("sl")
+        .args(vec!["resolve", "--list"])
+        .output()?;
+    if !output.status.success() {
+        return Err(output_text(&output).into());
+    }
+    Ok(ConflictedFile::parse_list(&String::from_utf8(
+        output.stdout,
+    )?))
+}
+
+/// Mark a conflicted file as resolved, or as unresolved again.
+pub fn sl_resolve_mark(path: &str, resolved: bool) -> Result<std::process::Output, std::io::Error> {
+    let flag = if resolved { "--mark" } else { "--unmark" };
+    Command::new("sl")
+        .args(vec!["resolve", flag, path])
+        .output()
+}
+
+/// The operation Sapling stopped in the middle of, if any, from the state files in its `.sl`
+/// (or `.hg`) directory.
+pub fn sl_unfinished_operation() -> Result<Option<UnfinishedOperation>, Box<dyn std::error::Error>>
+{
+    let output = Command::new("sl").args(vec!["root"]).output()?;
+    if !output.status.success() {
+        return Err(output_text(&output).into());
+    }
+    let root = PathBuf::from(String::from_utf8(output.stdout)?.trim_end());
+    Ok([".sl", ".hg"]
+        .iter()
+        .map(|dot_dir| root.join(dot_dir))
+        .find(|dot_dir| dot_dir.is_dir())
+        .and_then(|dot_dir| UnfinishedOperation::detect(&dot_dir)))
+}
+
+pub fn sl_continue() -> Result<std::process::Output, std::io::Error> {
+    Command::new("sl").args(vec!["continue"]).output()
+}
+
+pub fn sl_abort(operation: UnfinishedOperation) -> Result<std::process::Output, std::io::Error> {
+    Command::new("sl").args(operation.abort_args()).output()
+}
+
+/// The most recent `limit` moves of the working copy and bookmarks, newest first.
+pub fn sl_journal(limit: usize) -> Result<Vec<JournalEntry>, Box<dyn std::error::Error>> {
+    let limit = limit.to_string();
     let output = Command::new("sl")
-        .args(vec!["ssl", "--color=always"])
+        .args(vec![
+            "journal",
+            "--all",
+            "--limit",
+            &limit,
+            "-T",
+            &journal::template(),
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Err(output_text(&output).into());
+    }
+    Ok(JournalEntry::parse_list(&String::from_utf8(output.stdout)?))
+}
+
+/// The smartlog including `node`, even if it has since been hidden.
+pub fn sl_smartlog_at(
+    settings: &SmartlogSettings,
+    node: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let revset = settings.revset.as_deref().unwrap_or("smartlog()");
+    let mut args = settings.command.clone();
+    args.extend([
+        "--hidden".to_string(),
+        "--rev".to_string(),
+        format!("({}) + {}", revset, node),
+        color_flag().to_string(),
+    ]);
+    let output = Command::new("sl").args(args).output()?;
+    if !output.status.success() {
+        return Err(output_text(&output).into());
+    }
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .map(String::from)
+        .collect())
+}
+
+/// Run `sl` with arbitrary arguments, e.g. ones built by [`JournalEntry::bookmark_restore_args`].
+pub fn sl_run(args: &[String]) -> Result<std::process::Output, std::io::Error> {
+    Command::new("sl").args(args).output()
+}
+
+/// Every local and remote bookmark, local ones first.
+pub fn sl_bookmarks() -> Result<Vec<Bookmark>, Box<dyn std::error::Error>> {
+    let output = match sl_log(bookmarks::revset(true), &bookmarks::template(true)) {
+        Ok(output) => output,
+        // without remotenames support, only local bookmarks are listed
+        Err(_) => sl_log(bookmarks::revset(false), &bookmarks::template(false))?,
+    };
+    Ok(Bookmark::parse_list(&output))
+}
+
+/// Create a bookmark on `rev`, failing if it already exists.
+pub fn sl_bookmark_create(name: &str, rev: &str) -> Result<std::process::Output, std::io::Error> {
+    Command::new("sl")
+        .args(vec!["bookmark", "--rev", rev, name])
         .output()
-        .expect("Can't get repo smartlog");
+}
+
+/// Move an existing bookmark to `rev`.
+pub fn sl_bookmark_move(name: &str, rev: &str) -> Result<std::process::Output, std::io::Error> {
+    Command::new("sl")
+        .args(vec!["bookmark", "--force", "--rev", rev, name])
+        .output()
+}
+
+pub fn sl_bookmark_delete(name: &str) -> Result<std::process::Output, std::io::Error> {
+    Command::new("sl")
+        .args(vec!["bookmark", "--delete", name])
+        .output()
+}
+
+/// Undo the last `step` repository operations, or with `preview` only show the smartlog as it
+/// would be afterwards.
+pub fn sl_undo(step: usize, preview: bool) -> Result<std::process::Output, std::io::Error> {
+    let step = step.to_string();
+    let mut args = vec!["undo", "--step", &step];
+    if preview {
+        args.push("--preview");
+    }
+    Command::new("sl").args(args).output()
+}
+
+/// Redo the last undone operation, or with `preview` only show the smartlog as it would be.
+pub fn sl_redo(preview: bool) -> Result<std::process::Output, std::io::Error> {
+    let mut args = vec!["redo"];
+    if preview {
+        args.push("--preview");
+    }
+    Command::new("sl").args(args).output()
+}
+
+/// Copy `hashes` onto the working copy's parent, in that order.
+pub fn sl_graft(hashes: &[&str]) -> Result<std::process::Output, std::io::Error> {
+    Command::new("sl").arg("graft").args(hashes).output()
+}
+
+/// Replace the message of `hash` with the contents of `message_file`.
+pub fn sl_metaedit(
+    hash: &str,
+    message_file: &Path,
+) -> Result<std::process::Output, std::io::Error> {
+    Command::new("sl")
+        .args(["metaedit", "-r", hash, "-l"])
+        .arg(message_file)
+        .output()
+}
+
+/// The combined stdout and stderr of a command, as the user would have seen it in a terminal.
+pub fn output_text(output: &std::process::Output) -> String {
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    text
+}
+
+/// Run `sl log` on `revset` with a custom template, returning its output.
+pub fn sl_log(revset: &str, template: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("sl")
+        .args(vec!["log", "-r", revset, "-T", template])
+        .output()?;
     if !output.status.success() {
-        return Err(String::from_utf8(output.stderr).unwrap().into());
+        return Err(output_text(&output).into());
     }
-    let result = String::from_utf8(output.stdout)
-        .unwrap()
-        .split('\n')
-        .map(|x| x.to_string())
-        .collect();
-    Ok(result)
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Read a section of Sapling's config (e.g. `sl config sl-up`) as `(key, value)` pairs,
+/// with the section name stripped from the keys.
+pub fn sl_config(section: &str) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let output = Command::new("sl").args(vec!["config", section]).output()?;
+    // `sl config` exits with 1 when the section is empty
+    if !output.status.success() && !output.stderr.is_empty() {
+        return Err(output_text(&output).into());
+    }
+    Ok(parse_config_output(
+        section,
+        &String::from_utf8(output.stdout)?,
+    ))
+}
+
+fn parse_config_output(section: &str, output: &str) -> Vec<(String, String)> {
+    let prefix = format!("{}.", section);
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix(&prefix)?.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
 }
 
-pub fn sl_goto(hash: &str) -> Result<std::process::Output, std::io::Error> {
-    Command::new("sl").args(vec!["goto", hash]).output()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config_output() {
+        let output = "sl-up.revset=draft() + .\nsl-up.keymap.quit=q, esc\nsl-upx.other=1\n";
+        assert_eq!(
+            parse_config_output("sl-up", output),
+            vec![
+                ("revset".to_string(), "draft() + .".to_string()),
+                ("keymap.quit".to_string(), "q, esc".to_string()),
+            ]
+        );
+        assert!(parse_config_output("sl-up", "").is_empty());
+    }
+
+    #[test]
+    fn test_stack_move_args() {
+        assert_eq!(StackMove::Next(2).args(), vec!["next", "2"]);
+        assert_eq!(StackMove::Bottom.args(), vec!["prev", "--bottom"]);
+        assert!(StackMove::Top.goes_up());
+        assert!(!StackMove::Prev(1).goes_up());
+    }
+
+    #[test]
+    fn test_parse_heads() {
+        let output = "1cee5d55e\u{1f}fix the thing\u{1e}\nc3bd9e5fa\u{1f}\u{1e}";
+        assert_eq!(
+            parse_heads(output),
+            vec![
+                ("1cee5d55e".to_string(), "fix the thing".to_string()),
+                ("c3bd9e5fa".to_string(), String::new()),
+            ]
+        );
+        assert!(parse_heads("").is_empty());
+        assert!(is_ambiguous_next("abort: ambiguous next commit"));
+        assert!(!is_ambiguous_next("abort: uncommitted changes"));
+    }
 }
@@ -1,30 +1,158 @@
+use std::{
+    collections::HashSet,
+    error::Error,
+    ops::Range,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread::{self, JoinHandle},
+};
+
 use crate::{
-    graph::{Item, ItemType},
+    graph::{Commit, CommitState, Item, ItemType},
     parser::SmartLogParser,
 };
 
 #[derive(Debug)]
 pub struct SmartLog {
-    pub items: Vec<ItemType>,
+    items: Vec<ItemType>,
+    /// The index of the line each item starts on once rendered, kept up to date as items are
+    /// added so rendering and scrolling don't add up every item above the screen.
+    line_starts: Vec<usize>,
     selection_idx: usize,
+    /// Whether the selection was made up for lack of one in Sapling's colors, and should give
+    /// way to the commit Sapling selects if it shows up later.
+    fallback_selection: bool,
+    /// The hashes of the commits marked to act on several at once.
+    marked: HashSet<String>,
 }
 
 impl SmartLog {
     pub fn new(raw_lines: &[String]) -> Self {
-        let items = SmartLogParser::parse(raw_lines).unwrap();
-        let selection_idx = Self::get_selected_item_index(&items).unwrap();
-        Self {
-            items,
-            selection_idx,
+        Self::from_items(SmartLogParser::new(raw_lines))
+    }
+
+    /// Build the smartlog from items as they're parsed, e.g. while `sl` is still printing.
+    pub fn from_items(items: impl IntoIterator<Item = ItemType>) -> Self {
+        let mut smartlog = Self {
+            items: Vec::new(),
+            line_starts: Vec::new(),
+            selection_idx: 0,
+            fallback_selection: false,
+            marked: HashSet::new(),
+        };
+        smartlog.extend(items);
+        smartlog
+    }
+
+    /// Add items at the bottom of the graph.
+    pub fn extend(&mut self, items: impl IntoIterator<Item = ItemType>) {
+        for item in items {
+            let selected = matches!(&item, ItemType::Commit(commit) if commit.selected);
+            let has_selection = self.has_selection();
+            self.line_starts.push(self.line_count());
+            self.items.push(item);
+            let idx = self.items.len() - 1;
+            if !selected {
+                continue;
+            }
+            if has_selection && !self.fallback_selection {
+                // only one commit can be selected
+                self.deselect_line_idx(idx);
+                continue;
+            }
+            if self.fallback_selection {
+                self.deselect_line_idx(self.selection_idx);
+                self.fallback_selection = false;
+            }
+            self.selection_idx = idx;
+        }
+
+        // without colors, nothing looks selected: start from the working copy instead
+        if self.has_selection() && !self.fallback_selection {
+            return;
         }
+        let working_copy = self.commit_indices().find(|idx| {
+            matches!(&self.items[*idx], ItemType::Commit(commit) if commit.state == CommitState::WorkingCopy)
+        });
+        let Some(idx) = working_copy.or_else(|| self.commit_indices().next()) else {
+            return;
+        };
+        if self.has_selection() {
+            self.deselect_line_idx(self.selection_idx);
+        }
+        self.select_line_index(idx);
+        self.fallback_selection = true;
     }
 
-    pub fn get_selected_commit_hash(&self) -> Option<&str> {
-        let item = self.items.get(self.selection_idx).unwrap();
-        if let ItemType::Commit(commit) = item {
-            return commit.hash();
+    pub fn items(&self) -> &[ItemType] {
+        &self.items
+    }
+
+    fn has_selection(&self) -> bool {
+        self.selected_commit().is_some_and(|commit| commit.selected)
+    }
+
+    /// Whether there's any commit to select.
+    pub fn has_commits(&self) -> bool {
+        self.commit_indices().next().is_some()
+    }
+
+    /// Mark the selected commit, or unmark it if it's already marked.
+    pub fn toggle_mark(&mut self) {
+        let Some(hash) = self.get_selected_commit_hash().map(String::from) else {
+            return;
+        };
+        if !self.marked.remove(&hash) {
+            self.marked.insert(hash);
         }
-        None
+    }
+
+    pub fn is_marked(&self, hash: &str) -> bool {
+        self.marked.contains(hash)
+    }
+
+    /// The marked commits, from top to bottom.
+    pub fn marked_hashes(&self) -> Vec<&str> {
+        self.commit_hashes()
+            .into_iter()
+            .filter(|hash| self.marked.contains(*hash))
+            .collect()
+    }
+
+    /// Mark `hashes`, ignoring the ones not in the graph.
+    pub fn set_marks(&mut self, hashes: &[&str]) {
+        let present: HashSet<&str> = self.commit_hashes().into_iter().collect();
+        self.marked = hashes
+            .iter()
+            .filter(|hash| present.contains(*hash))
+            .map(|hash| hash.to_string())
+            .collect();
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+    }
+
+    /// The selected commit.
+    pub fn selected_commit(&self) -> Option<&Commit> {
+        match self.items.get(self.selection_idx)? {
+            ItemType::Commit(commit) => Some(commit),
+            _ => None,
+        }
+    }
+
+    /// The hashes of every commit in the graph, from top to bottom.
+    pub fn commit_hashes(&self) -> Vec<&str> {
+        self.items
+            .iter()
+            .filter_map(|item| match item {
+                ItemType::Commit(commit) => commit.hash(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn get_selected_commit_hash(&self) -> Option<&str> {
+        self.selected_commit()?.hash()
     }
 
     pub fn move_up(&mut self) {
@@ -46,7 +174,7 @@ impl SmartLog {
     }
 
     pub fn move_down(&mut self) {
-        if self.selection_idx < self.items.len() - 1 {
+        if self.selection_idx + 1 < self.items.len() {
             let mut selection_candidate = self.selection_idx;
             for i in (self.selection_idx + 1)..self.items.len() {
                 if let ItemType::Commit(_) = self.items[i] {
@@ -63,6 +191,131 @@ impl SmartLog {
         }
     }
 
+    /// Move the selection `commits` commits down, or up when negative.
+    pub fn move_by_commits(&mut self, commits: isize) {
+        for _ in 0..commits.unsigned_abs() {
+            if commits > 0 {
+                self.move_down();
+            } else {
+                self.move_up();
+            }
+        }
+    }
+
+    /// Move the selection to the first commit in the graph.
+    pub fn move_to_top(&mut self) {
+        let first = self.commit_indices().next();
+        if let Some(idx) = first {
+            self.move_selection_to(idx);
+        }
+    }
+
+    /// Move the selection to the last commit in the graph.
+    pub fn move_to_bottom(&mut self) {
+        let last = self.commit_indices().last();
+        if let Some(idx) = last {
+            self.move_selection_to(idx);
+        }
+    }
+
+    /// Move the selection by roughly `lines` rendered lines (up when negative), landing on the
+    /// commit closest to that line. Always moves at least one commit if possible.
+    pub fn move_by_lines(&mut self, lines: isize) {
+        let starts = self.item_line_starts();
+        let Some(current) = starts.get(self.selection_idx).map(|start| *start as isize) else {
+            return;
+        };
+        let target = (current + lines).clamp(0, self.line_count() as isize);
+        let candidate = self
+            .commit_indices()
+            .min_by_key(|idx| (starts[*idx] as isize - target).abs())
+            .unwrap_or(self.selection_idx);
+
+        if candidate != self.selection_idx {
+            self.move_selection_to(candidate);
+        } else if lines > 0 {
+            self.move_down();
+        } else if lines < 0 {
+            self.move_up();
+        }
+    }
+
+    /// Select the commit rendered on `line`, returning its item index.
+    /// Returns `None`, leaving the selection unchanged, if that line isn't part of a commit.
+    pub fn select_commit_at_line(&mut self, line: usize) -> Option<usize> {
+        let item_idx = self.item_index_at_line(line)?;
+        if !matches!(self.items[item_idx], ItemType::Commit(_)) {
+            return None;
+        }
+        self.move_selection_to(item_idx);
+        Some(item_idx)
+    }
+
+    /// Select the commit whose hash is a prefix of `node`, or the other way around, so full and
+    /// short hashes both match. Returns whether it's in the graph.
+    pub fn select_commit_by_hash(&mut self, node: &str) -> bool {
+        let found = self.commit_indices().find(|idx| match &self.items[*idx] {
+            ItemType::Commit(commit) => commit
+                .hash()
+                .is_some_and(|hash| hash.starts_with(node) || node.starts_with(hash)),
+            _ => false,
+        });
+        match found {
+            Some(idx) => {
+                self.move_selection_to(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The index of the item rendered on `line`.
+    pub fn item_index_at_line(&self, line: usize) -> Option<usize> {
+        let starts = self.item_line_starts();
+        if line >= self.line_count() {
+            return None;
+        }
+        Some(starts.partition_point(|start| *start <= line) - 1)
+    }
+
+    /// The number of rendered lines.
+    pub fn line_count(&self) -> usize {
+        match (self.line_starts.last(), self.items.last()) {
+            (Some(start), Some(item)) => start + item.parsed_lines().len(),
+            _ => 0,
+        }
+    }
+
+    /// The rendered lines occupied by the selected commit.
+    pub fn selected_line_range(&self) -> Range<usize> {
+        let Some(commit) = self.selected_commit() else {
+            return 0..0;
+        };
+        let start = self.item_line_starts()[self.selection_idx];
+        start..start + commit.parsed_lines().len()
+    }
+
+    /// The index of the line each item starts on once rendered.
+    pub fn item_line_starts(&self) -> &[usize] {
+        &self.line_starts
+    }
+
+    fn commit_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| matches!(item, ItemType::Commit(_)))
+            .map(|(idx, _)| idx)
+    }
+
+    fn move_selection_to(&mut self, item_idx: usize) {
+        if item_idx == self.selection_idx {
+            return;
+        }
+        self.deselect_line_idx(self.selection_idx);
+        self.select_line_index(item_idx);
+    }
+
     pub fn to_string_vec(&self) -> Vec<String> {
         self.items
             .iter()
@@ -75,6 +328,7 @@ impl SmartLog {
         if let ItemType::Commit(commit) = item {
             commit.select();
             self.selection_idx = item_idx;
+            self.fallback_selection = false;
         }
     }
 
@@ -84,22 +338,98 @@ impl SmartLog {
             commit.deselect();
         }
     }
+}
 
-    fn get_selected_item_index(items: &[ItemType]) -> Option<usize> {
-        for (idx, item) in items.iter().enumerate() {
-            if let ItemType::Commit(commit) = item {
-                if commit.selected {
-                    return Some(idx);
+/// The items of a smartlog parsed on their own thread while `sl` prints it, to add to the
+/// smartlog as they come.
+pub struct SmartlogFeed {
+    items: Receiver<ItemType>,
+    /// Parses the lines, then checks how `sl` exited.
+    thread: JoinHandle<Result<(), String>>,
+}
+impl SmartlogFeed {
+    /// Parse `lines` on a new thread, then `finish` them, e.g. waiting for `sl` to exit.
+    pub fn spawn<L>(
+        mut lines: L,
+        finish: impl FnOnce(L) -> Result<(), String> + Send + 'static,
+    ) -> Self
+    where
+        L: Iterator<Item = String> + Send + 'static,
+    {
+        let (sender, items) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            for item in SmartLogParser::new(&mut lines) {
+                // nobody wants the rest, e.g. the smartlog was reloaded
+                if sender.send(item).is_err() {
+                    break;
                 }
             }
+            finish(lines)
+        });
+        Self { items, thread }
+    }
+
+    /// The next item, waiting for it to be parsed. `None` once every item was handed out.
+    pub fn next_item(&self) -> Option<ItemType> {
+        self.items.recv().ok()
+    }
+
+    /// The items parsed since last asked, without waiting. `None` once every item was handed
+    /// out.
+    pub fn ready_items(&self) -> Option<Vec<ItemType>> {
+        let mut items = Vec::new();
+        loop {
+            match self.items.try_recv() {
+                Ok(item) => items.push(item),
+                Err(TryRecvError::Disconnected) if items.is_empty() => return None,
+                Err(_) => return Some(items),
+            }
+        }
+    }
+
+    /// Wait for the lines to be finished, once every item was handed out.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self.thread.join() {
+            Ok(result) => Ok(result?),
+            Err(_) => Err("the smartlog couldn't be parsed".into()),
         }
-        None
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
+    use crate::parser::tests::smartlog_lines;
+
+    proptest! {
+        #[test]
+        fn renders_the_smartlog_as_printed(lines in smartlog_lines()) {
+            prop_assert_eq!(SmartLog::new(&lines).to_string_vec(), lines);
+        }
+
+        #[test]
+        fn moves_back_to_the_smartlog_as_printed(
+            lines in smartlog_lines(),
+            moves in prop::collection::vec(-30_isize..30, 0..10),
+        ) {
+            let mut smartlog = SmartLog::new(&lines);
+            let rendered = smartlog.to_string_vec();
+            let selected = smartlog.selection_idx;
+            for lines in moves {
+                smartlog.move_by_lines(lines);
+                prop_assert!(smartlog.selected_line_range().end <= smartlog.line_count());
+            }
+            smartlog.move_to_top();
+            smartlog.move_to_bottom();
+            if smartlog.has_commits() {
+                smartlog.select_commit_at_line(0);
+                smartlog.move_selection_to(selected);
+            }
+            prop_assert_eq!(smartlog.to_string_vec(), rendered);
+        }
+    }
 
     const RAW_LINES: [&str; 15] = [
         "  @  \u{1b}[0;35m\u{1b}[0;93;1m1cee5d55e\u{1b}[0m\u{1b}[0;35m  Dec 08 at 09:46  royrothenberg  \u{1b}[0;36m#780 Closed\u{1b}[0m\u{1b}[0;35m \u{1b}[0;31m✗\u{1b}[0m",
@@ -155,6 +485,116 @@ mod tests {
         assert_eq!(smartlog.selection_idx, 8);
     }
 
+    #[test]
+    fn test_move_by_commits() {
+        let mut smartlog = SmartLog::new(&raw_lines());
+        smartlog.move_by_commits(3);
+        assert_eq!(smartlog.selection_idx, 6);
+        smartlog.move_by_commits(-2);
+        assert_eq!(smartlog.selection_idx, 2);
+        smartlog.move_by_commits(10);
+        assert_eq!(smartlog.selection_idx, 8);
+    }
+
+    #[test]
+    fn test_jumps() {
+        let mut smartlog = SmartLog::new(&raw_lines());
+        smartlog.move_to_bottom();
+        assert_eq!(smartlog.selection_idx, 8);
+        assert_eq!(smartlog.selected_line_range(), 11..12);
+        smartlog.move_to_top();
+        assert_eq!(smartlog.selection_idx, 0);
+        assert_eq!(smartlog.selected_line_range(), 0..2);
+    }
+
+    #[test]
+    fn test_move_by_lines() {
+        let mut smartlog = SmartLog::new(&raw_lines());
+        // a small distance still moves to the next commit
+        smartlog.move_by_lines(1);
+        assert_eq!(smartlog.selection_idx, 2);
+        smartlog.move_by_lines(-1);
+        assert_eq!(smartlog.selection_idx, 0);
+        // line 6 holds the remote/main commit
+        smartlog.move_by_lines(6);
+        assert_eq!(smartlog.selection_idx, 4);
+        smartlog.move_by_lines(100);
+        assert_eq!(smartlog.selection_idx, 8);
+        smartlog.move_by_lines(-100);
+        assert_eq!(smartlog.selection_idx, 0);
+    }
+
+    #[test]
+    fn test_select_commit_at_line() {
+        let mut smartlog = SmartLog::new(&raw_lines());
+        assert_eq!(smartlog.item_index_at_line(0), Some(0));
+        assert_eq!(smartlog.item_index_at_line(1), Some(0));
+        assert_eq!(smartlog.item_index_at_line(2), Some(1));
+        assert_eq!(smartlog.item_index_at_line(14), Some(11));
+        assert_eq!(smartlog.item_index_at_line(15), None);
+
+        // second line of the c3bd9e5fa commit
+        assert_eq!(smartlog.select_commit_at_line(4), Some(2));
+        assert_eq!(smartlog.get_selected_commit_hash().unwrap(), "c3bd9e5fa");
+        // a glyph
+        assert_eq!(smartlog.select_commit_at_line(5), None);
+        assert_eq!(smartlog.selection_idx, 2);
+        assert_eq!(smartlog.select_commit_at_line(100), None);
+    }
+
+    #[test]
+    fn test_select_commit_by_hash() {
+        let mut smartlog = SmartLog::new(&raw_lines());
+        assert!(smartlog.select_commit_by_hash("ba27d4d13fedcba9876543210fedcba987654321"));
+        assert_eq!(smartlog.selection_idx, 4);
+        assert!(smartlog.select_commit_by_hash("c3bd"));
+        assert_eq!(smartlog.selection_idx, 2);
+        assert!(!smartlog.select_commit_by_hash("deadbeef"));
+        assert_eq!(smartlog.selection_idx, 2);
+    }
+
+    #[test]
+    fn test_marks() {
+        let mut smartlog = SmartLog::new(&raw_lines());
+        smartlog.toggle_mark();
+        smartlog.move_by_commits(2);
+        smartlog.toggle_mark();
+        assert!(smartlog.is_marked("ba27d4d13"));
+        assert_eq!(smartlog.marked_hashes(), vec!["1cee5d55e", "ba27d4d13"]);
+        smartlog.toggle_mark();
+        assert_eq!(smartlog.marked_hashes(), vec!["1cee5d55e"]);
+
+        smartlog.set_marks(&["c3bd9e5fa", "deadbeef"]);
+        assert_eq!(smartlog.marked_hashes(), vec!["c3bd9e5fa"]);
+        smartlog.clear_marks();
+        assert!(smartlog.marked_hashes().is_empty());
+    }
+
+    #[test]
+    fn test_commit_hashes() {
+        let smartlog = SmartLog::new(&raw_lines());
+        assert_eq!(
+            smartlog.commit_hashes(),
+            vec![
+                "1cee5d55e",
+                "c3bd9e5fa",
+                "ba27d4d13",
+                "2f85065e7",
+                "0e069ab09"
+            ]
+        );
+        assert_eq!(
+            smartlog.selected_commit().unwrap().hash(),
+            Some("1cee5d55e")
+        );
+    }
+
+    #[test]
+    fn test_line_count() {
+        let smartlog = SmartLog::new(&raw_lines());
+        assert_eq!(smartlog.line_count(), 15);
+    }
+
     #[test]
     fn test_to_string_vec() {
         let smartlog = SmartLog::new(&raw_lines());
@@ -162,6 +602,110 @@ mod tests {
         assert_eq!(string_vec.len(), 15);
     }
 
+    /// [`RAW_LINES`] as printed with `--color=never`.
+    const PLAIN_LINES: [&str; 15] = [
+        "  @  1cee5d55e  Dec 08 at 09:46  royrothenberg  #780 Closed ✗",
+        "  │  [pr body update] update stack list without overwriting PR title and body",
+        "  │",
+        "  o  c3bd9e5fa  Dec 08 at 09:46  royrothenberg  #779 Unreviewed ✗",
+        "╭─╯  [pr body update] fix reviewstack option breaking stack list detection",
+        "│",
+        "o  ba27d4d13  Dec 07 at 22:20  remote/main",
+        "╷",
+        "╷ o  2f85065e7  Nov 28 at 11:49  royrothenberg  #781 Closed ✓",
+        "╭─╯  [isl] increase width of diff window in split stack edit panel",
+        "│",
+        "o  0e069ab09  Nov 21 at 13:16",
+        "│",
+        "~",
+        "",
+    ];
+
+    #[test]
+    fn test_plain_lines() {
+        let lines: Vec<String> = PLAIN_LINES.iter().map(|x| x.to_string()).collect();
+        let mut smartlog = SmartLog::new(&lines);
+        assert_eq!(smartlog.get_selected_commit_hash(), Some("1cee5d55e"));
+        assert_eq!(
+            smartlog.commit_hashes(),
+            vec![
+                "1cee5d55e",
+                "c3bd9e5fa",
+                "ba27d4d13",
+                "2f85065e7",
+                "0e069ab09"
+            ]
+        );
+        smartlog.move_down();
+        assert_eq!(smartlog.get_selected_commit_hash(), Some("c3bd9e5fa"));
+        smartlog.move_up();
+        assert_eq!(smartlog.get_selected_commit_hash(), Some("1cee5d55e"));
+        assert_eq!(smartlog.line_count(), 15);
+    }
+
+    #[test]
+    fn test_extend() {
+        let lines = raw_lines();
+        let mut smartlog = SmartLog::from_items(SmartLogParser::new(&lines[..4]));
+        assert_eq!(smartlog.get_selected_commit_hash(), Some("1cee5d55e"));
+        smartlog.extend(SmartLogParser::new(&lines[4..]));
+        assert_eq!(smartlog.commit_hashes().len(), 5);
+        assert_eq!(smartlog.get_selected_commit_hash(), Some("1cee5d55e"));
+        let lengths = smartlog.items.iter().map(|item| item.parsed_lines().len());
+        let starts: Vec<usize> = lengths
+            .scan(0, |line, len| {
+                *line += len;
+                Some(*line - len)
+            })
+            .collect();
+        assert_eq!(smartlog.item_line_starts(), starts);
+        assert_eq!(smartlog.line_count(), 15);
+
+        // a commit selected by Sapling's colors takes over from the first commit
+        let mut smartlog = SmartLog::from_items(SmartLogParser::new(&lines[3..6]));
+        assert_eq!(smartlog.get_selected_commit_hash(), Some("c3bd9e5fa"));
+        smartlog.extend(SmartLogParser::new(&lines[..3]));
+        assert_eq!(smartlog.get_selected_commit_hash(), Some("1cee5d55e"));
+        assert!(matches!(&smartlog.items[0], ItemType::Commit(commit) if !commit.selected));
+        assert_eq!(smartlog.items[0].to_string_vec(), lines[3..5]);
+    }
+
+    #[test]
+    fn test_feed() {
+        let feed = SmartlogFeed::spawn(raw_lines().into_iter(), |_| Ok(()));
+        let mut smartlog = SmartLog::from_items(feed.next_item());
+        while let Some(items) = feed.ready_items() {
+            smartlog.extend(items);
+        }
+        assert!(feed.finish().is_ok());
+        assert_eq!(
+            smartlog.to_string_vec(),
+            SmartLog::new(&raw_lines()).to_string_vec()
+        );
+
+        let feed = SmartlogFeed::spawn(Vec::new().into_iter(), |_| Err("not a repo".into()));
+        assert!(feed.next_item().is_none());
+        assert_eq!(feed.finish().unwrap_err().to_string(), "not a repo");
+    }
+
+    #[test]
+    fn test_no_commits() {
+        let lines = vec!["~".to_string(), String::new()];
+        let mut smartlog = SmartLog::new(&lines);
+        assert!(!smartlog.has_commits());
+        assert_eq!(smartlog.get_selected_commit_hash(), None);
+        smartlog.move_down();
+        smartlog.move_by_lines(10);
+        smartlog.move_to_bottom();
+        assert_eq!(smartlog.selected_line_range(), 0..0);
+
+        let mut smartlog = SmartLog::new(&[]);
+        smartlog.move_up();
+        smartlog.move_down();
+        smartlog.move_by_lines(-3);
+        assert_eq!(smartlog.selected_line_range(), 0..0);
+    }
+
     fn raw_lines() -> Vec<String> {
         RAW_LINES.iter().map(|x| x.to_string()).collect()
     }
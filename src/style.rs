@@ -0,0 +1,241 @@
+//! Highlighting drawn over the smartlog's lines as they're rendered.
+//!
+//! The lines keep Sapling's escape codes as it printed them. The selection, marks and commit
+//! states are drawn on top as [`Overlay`]s, applied again after each of Sapling's resets so
+//! they last across the whole line.
+//!
+
+pub const RESET: &str = "\u{1b}[0m";
+pub const REVERSE: &str = "\u{1b}[7m";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Overlay {
+    /// Color the text after the graph wherever Sapling left it uncolored, the way Sapling
+    /// highlights the working copy.
+    Text(String),
+    /// Style the whole line on top of Sapling's colors, e.g. dimmed.
+    Line(String),
+    /// Like [`Overlay::Line`], filling the row up to the screen's width, e.g. for reverse
+    /// video or a background color.
+    Row(String),
+    /// Style one block of the line, e.g. a marked hash.
+    Block(usize, String),
+}
+
+/// Render the `blocks` of a line, as split by the parser, with `overlays` on top. Rows are
+/// filled up to `width` columns.
+///
+/// ```
+/// # use sl_up::style::{render, Overlay};
+/// let blocks = ["o  ", "\u{1b}[0;33m", "ba27d4d13", "\u{1b}[0m", "  remote/main"].map(String::from);
+/// assert_eq!(render(&blocks, &[], 80), blocks.concat());
+/// assert_eq!(
+///     render(&blocks, &[Overlay::Text("\u{1b}[0;35m".to_string())], 80),
+///     "o  \u{1b}[0;35m\u{1b}[0;33mba27d4d13\u{1b}[0m\u{1b}[0;35m  remote/main\u{1b}[0m"
+/// );
+/// ```
+pub fn render(blocks: &[String], overlays: &[Overlay], width: usize) -> String {
+    if overlays.is_empty() {
+        return blocks.concat();
+    }
+    let line_style: String = overlays
+        .iter()
+        .filter_map(|overlay| match overlay {
+            Overlay::Line(style) | Overlay::Row(style) => Some(style.as_str()),
+            _ => None,
+        })
+        .collect();
+    let text_style = overlays
+        .iter()
+        .find_map(|overlay| match overlay {
+            Overlay::Text(style) => Some(style.as_str()),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let block_style = |idx: usize| {
+        overlays.iter().find_map(|overlay| match overlay {
+            Overlay::Block(block, style) if *block == idx => Some(style.as_str()),
+            _ => None,
+        })
+    };
+    let fill = overlays
+        .iter()
+        .any(|overlay| matches!(overlay, Overlay::Row(_)));
+    // the graph is the first block, unless the line starts with an escape code
+    let graph_end = usize::from(blocks.first().is_some_and(|block| !is_escape(block)));
+
+    let mut line = line_style.clone();
+    // Sapling's escape codes since its last reset, to restore after a styled block
+    let mut active = String::new();
+    let mut columns = 0;
+    for (idx, block) in blocks.iter().enumerate() {
+        if idx == graph_end {
+            line.push_str(text_style);
+        }
+        if is_escape(block) {
+            line.push_str(block);
+            if resets(block) {
+                active.clear();
+                line.push_str(&line_style);
+                if is_reset(block) && idx >= graph_end {
+                    line.push_str(text_style);
+                }
+            }
+            active.push_str(block);
+            continue;
+        }
+        columns += block.chars().count();
+        match block_style(idx) {
+            Some(style) => {
+                line.push_str(style);
+                line.push_str(block);
+                line.push_str(RESET);
+                line.push_str(&line_style);
+                if idx >= graph_end {
+                    line.push_str(text_style);
+                }
+                line.push_str(&active);
+            }
+            None => line.push_str(block),
+        }
+    }
+    if blocks.len() == graph_end {
+        line.push_str(text_style);
+    }
+    if fill {
+        line.push_str(&" ".repeat(width.saturating_sub(columns)));
+    }
+    line.push_str(RESET);
+    line
+}
+
+/// The background version of a foreground color escape code, e.g. for a selected row.
+///
+/// ```
+/// # use sl_up::style::background;
+/// assert_eq!(background("\u{1b}[0;35m"), "\u{1b}[0;45m");
+/// assert_eq!(background("\u{1b}[0;94m"), "\u{1b}[0;104m");
+/// assert_eq!(background("\u{1b}[0;38;2;0;255;0m"), "\u{1b}[0;48;2;0;255;0m");
+/// ```
+pub fn background(color: &str) -> String {
+    let Some(params) = sgr_params(color) else {
+        return color.to_string();
+    };
+    let mut params: Vec<String> = params.split(';').map(String::from).collect();
+    let mut idx = 0;
+    while idx < params.len() {
+        match params[idx].parse::<u8>() {
+            // extended colors, with their own parameters after
+            Ok(38) => {
+                params[idx] = "48".to_string();
+                break;
+            }
+            Ok(code @ (30..=37 | 90..=97)) => params[idx] = (code + 10).to_string(),
+            _ => {}
+        }
+        idx += 1;
+    }
+    format!("\u{1b}[{}m", params.join(";"))
+}
+
+/// The parameters of an SGR escape code, e.g. `0;35` for `\x1b[0;35m`.
+fn sgr_params(block: &str) -> Option<&str> {
+    block.strip_prefix("\u{1b}[")?.strip_suffix('m')
+}
+
+fn is_escape(block: &str) -> bool {
+    block.starts_with('\u{1b}')
+}
+
+/// Whether the escape code resets every style, and nothing else.
+fn is_reset(block: &str) -> bool {
+    matches!(sgr_params(block), Some("" | "0"))
+}
+
+/// Whether the escape code resets every style before setting its own.
+fn resets(block: &str) -> bool {
+    sgr_params(block)
+        .is_some_and(|params| params.is_empty() || params == "0" || params.starts_with("0;"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAGENTA: &str = "\u{1b}[0;35m";
+    const DIM: &str = "\u{1b}[2m";
+
+    fn blocks(blocks: &[&str]) -> Vec<String> {
+        blocks.iter().map(|block| block.to_string()).collect()
+    }
+
+    #[test]
+    fn test_text() {
+        // a line without colors
+        let line = blocks(&["│", "  fix the parser"]);
+        assert_eq!(
+            render(&line, &[Overlay::Text(MAGENTA.to_string())], 80),
+            format!("│{}  fix the parser{}", MAGENTA, RESET)
+        );
+        // a graph-only line
+        let line = blocks(&["│"]);
+        assert_eq!(
+            render(&line, &[Overlay::Text(MAGENTA.to_string())], 80),
+            format!("│{}{}", MAGENTA, RESET)
+        );
+    }
+
+    #[test]
+    fn test_line() {
+        let line = blocks(&["o  ", "\u{1b}[0;33m", "ba27d4d13", RESET, "  main"]);
+        assert_eq!(
+            render(&line, &[Overlay::Line(DIM.to_string())], 80),
+            format!(
+                "{}o  \u{1b}[0;33m{}ba27d4d13{}{}  main{}",
+                DIM, DIM, RESET, DIM, RESET
+            )
+        );
+    }
+
+    #[test]
+    fn test_row() {
+        let line = blocks(&["o  ", "\u{1b}[0;33m", "ba27d4d13", RESET]);
+        let rendered = render(&line, &[Overlay::Row(REVERSE.to_string())], 16);
+        assert_eq!(
+            rendered,
+            format!(
+                "{}o  \u{1b}[0;33m{}ba27d4d13{}{}    {}",
+                REVERSE, REVERSE, RESET, REVERSE, RESET
+            )
+        );
+        // too long to fill
+        let rendered = render(&line, &[Overlay::Row(REVERSE.to_string())], 4);
+        assert!(rendered.ends_with(&format!("{}{}", REVERSE, RESET)));
+    }
+
+    #[test]
+    fn test_block() {
+        let line = blocks(&["o  ", "\u{1b}[0;33m", "ba27d4d13", RESET, "  main"]);
+        let overlays = [
+            Overlay::Text(MAGENTA.to_string()),
+            Overlay::Block(2, REVERSE.to_string()),
+        ];
+        assert_eq!(
+            render(&line, &overlays, 80),
+            format!(
+                "o  {}\u{1b}[0;33m{}ba27d4d13{}{}\u{1b}[0;33m{}{}  main{}",
+                MAGENTA, REVERSE, RESET, MAGENTA, RESET, MAGENTA, RESET
+            )
+        );
+    }
+
+    #[test]
+    fn test_resets() {
+        assert!(is_reset(RESET));
+        assert!(is_reset("\u{1b}[m"));
+        assert!(!is_reset(MAGENTA));
+        assert!(resets(MAGENTA));
+        assert!(!resets(DIM));
+        assert!(!resets("\u{1b}[01;32m"));
+    }
+}
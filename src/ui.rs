@@ -1,64 +1,1671 @@
-use std::io::{self, Stdout, Write};
+use std::{
+    collections::HashMap,
+    io::{self, Stdout, Write},
+    ops::Range,
+    time::{Duration, Instant},
+};
 
+use ansi_parser::{AnsiParser, Output};
 use crossterm::{
-    cursor::{Hide, MoveTo, Show},
-    event::{Event, KeyCode},
-    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+    cursor::{self, Hide, MoveTo, Show},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
+    terminal::{
+        self, Clear, ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
     ExecutableCommand, QueueableCommand,
 };
 
-use crate::smartlog::SmartLog;
+use crate::{
+    bookmarks::{validate_name, Bookmark},
+    config::{Config, DefaultAction, Height, SelectionStyle},
+    conflicts::ConflictPanel,
+    details::DetailsCache,
+    editor::{editor_command, TempFile},
+    fold::{self, FoldRange, RangeCommit},
+    frame::{Frame, Screen},
+    graph::{CommitState, Item, ItemType, SAPLING_SELECTION_COLOR},
+    journal::JournalEntry,
+    keymap::{Action, Dispatch, KeyDispatcher, KeySequence},
+    picker::{fuzzy_positions, Picker},
+    sapling_cmd::{
+        is_ambiguous_next, output_text, sl_abort, sl_bookmark_create, sl_bookmark_delete,
+        sl_bookmark_move, sl_bookmarks, sl_continue, sl_goto, sl_graft, sl_journal, sl_log,
+        sl_metaedit, sl_next_heads, sl_redo, sl_resolve_list, sl_resolve_mark, sl_run, sl_shelve,
+        sl_smartlog, sl_smartlog_at, sl_stack_move, sl_status, sl_undo, sl_unfinished_operation,
+        sl_unshelve, GotoMode, StackMove,
+    },
+    smartlog::{SmartLog, SmartlogFeed},
+    style::{self, background, Overlay, RESET, REVERSE},
+};
 
-pub fn start_ui_and_get_selected_commit<'a>(smartlog: &'a mut SmartLog) -> Option<&'a str> {
-    let mut stdout = io::stdout();
-    terminal::enable_raw_mode().unwrap();
-    stdout.execute(EnterAlternateScreen).unwrap();
-    stdout.execute(Hide).unwrap();
-    render_smartlog(&mut stdout, smartlog);
+/// Run the interactive smartlog until the user quits or acts on a commit, adding the items
+/// still to come from `feed` as they're parsed.
+/// Returns the text to print once the UI is closed, such as the output of `sl goto`.
+pub fn start_ui(
+    smartlog: &mut SmartLog,
+    feed: Option<SmartlogFeed>,
+    config: &Config,
+) -> Option<String> {
+    let mut ui = Ui::new(config);
+    ui.feed = feed;
+    ui.enter_screen();
+    // a rebase or goto may have stopped with conflicts before we were started
+    ui.check_conflicts(smartlog);
+    // the working copy may be below the first screen
+    ui.scroll_to_selection(smartlog);
+    ui.render(smartlog);
 
-    let mut commit_hash: Option<&str> = None;
+    let mut exit_output = None;
     'terminal_ui: loop {
-        let input = crossterm::event::read().unwrap();
-        if let Event::Key(key_event) = input {
-            match key_event.code {
-                KeyCode::Char('q') | KeyCode::Esc => break 'terminal_ui,
-                KeyCode::Char('c') => {
-                    if key_event
-                        .modifiers
-                        .contains(crossterm::event::KeyModifiers::CONTROL)
-                    {
-                        break 'terminal_ui;
+        let input = ui.next_event(smartlog);
+        ui.status = None;
+        if ui.overlay.is_some() {
+            // any key closes the overlay
+            if let Event::Key(_) = input {
+                ui.overlay = None;
+            }
+            ui.render(smartlog);
+            continue;
+        }
+        let action = match input {
+            Event::Key(key_event) => match ui.dispatcher.feed(&config.keymap, &key_event) {
+                Dispatch::Action(action, count) => Some((action, count)),
+                Dispatch::Pending => {
+                    ui.status = Some(ui.dispatcher.pending_keys());
+                    None
+                }
+                Dispatch::Unbound => None,
+            },
+            Event::Mouse(mouse_event) => ui.handle_mouse(smartlog, &mouse_event),
+            _ => None,
+        };
+        match action {
+            Some((Action::Quit, _)) if ui.fold_anchor.is_some() => {
+                ui.fold_anchor = None;
+                ui.status = Some("Fold cancelled".to_string());
+            }
+            Some((Action::Quit, _)) => break 'terminal_ui,
+            Some((Action::Select, _)) if ui.confirm_select(smartlog) => {
+                exit_output = ui.run_default_action(smartlog);
+                if exit_output.is_some() {
+                    break 'terminal_ui;
+                }
+            }
+            Some((Action::EditMessage, _)) => ui.edit_message(smartlog),
+            Some((Action::Fold, _)) => ui.fold(smartlog),
+            Some((Action::ToggleMark, count)) => {
+                for _ in 0..count {
+                    smartlog.toggle_mark();
+                    smartlog.move_by_commits(1);
+                }
+                ui.scroll_to_selection(smartlog);
+            }
+            Some((Action::ClearMarks, _)) => smartlog.clear_marks(),
+            Some((Action::Graft, _)) => ui.graft(smartlog),
+            Some((Action::Next, count)) => ui.move_in_stack(smartlog, StackMove::Next(count)),
+            Some((Action::Prev, count)) => ui.move_in_stack(smartlog, StackMove::Prev(count)),
+            Some((Action::StackTop, _)) => ui.move_in_stack(smartlog, StackMove::Top),
+            Some((Action::StackBottom, _)) => ui.move_in_stack(smartlog, StackMove::Bottom),
+            Some((Action::Undo, count)) => {
+                let question = match count {
+                    1 => "Undo the last operation?".to_string(),
+                    _ => format!("Undo the last {} operations?", count),
+                };
+                ui.run_with_preview(smartlog, &question, sl_undo(count, true), || {
+                    sl_undo(count, false)
+                });
+            }
+            Some((Action::Redo, _)) => {
+                ui.run_with_preview(
+                    smartlog,
+                    "Redo the last undone operation?",
+                    sl_redo(true),
+                    || sl_redo(false),
+                );
+            }
+            Some((Action::Journal, _)) => ui.browse_journal(smartlog),
+            Some((Action::CreateBookmark, _)) => ui.create_bookmark(smartlog),
+            Some((Action::MoveBookmark, _)) => ui.move_bookmark(smartlog),
+            Some((Action::DeleteBookmark, _)) => ui.delete_bookmark(smartlog),
+            Some((Action::JumpToBookmark, _)) => ui.jump_to_bookmark(smartlog),
+            Some((Action::Help, _)) => ui.overlay = Some(ui.help_lines()),
+            Some((Action::ToggleDetails, _)) => {
+                ui.show_details = !ui.show_details;
+                ui.scroll_to_selection(smartlog);
+            }
+            Some((action, count)) => {
+                ui.navigate(smartlog, action, count);
+                ui.scroll_to_selection(smartlog);
+            }
+            None => {}
+        }
+        if let (Some(anchor), None) = (&ui.fold_anchor, &ui.status) {
+            ui.status = Some(format!(
+                "Folding from {}: move to the other end of the stack and fold again, or quit to cancel",
+                anchor
+            ));
+        }
+        ui.render(smartlog);
+    }
+
+    ui.leave_screen();
+    exit_output
+}
+
+/// Two clicks on the same commit within this delay make a double-click.
+const DOUBLE_CLICK_DELAY: Duration = Duration::from_millis(400);
+/// The number of lines scrolled by one step of the mouse wheel.
+const WHEEL_SCROLL_LINES: usize = 3;
+/// How often the smartlog is extended with the items parsed since, while `sl` prints it.
+const FEED_INTERVAL: Duration = Duration::from_millis(50);
+/// The actions listed in the hint bar, with their short labels.
+const HINTS: [(Action, &str); 5] = [
+    (Action::MoveUp, "up"),
+    (Action::MoveDown, "down"),
+    (Action::Select, "select"),
+    (Action::Help, "help"),
+    (Action::Quit, "quit"),
+];
+/// The number of journal entries listed.
+const JOURNAL_LIMIT: usize = 100;
+/// The most rows the detail panel takes, including its separator line.
+const MAX_DETAILS_HEIGHT: usize = 10;
+const DIM: &str = "\u{1b}[2m";
+const UNDERLINE: &str = "\u{1b}[4m";
+
+/// A key pressed in a panel such as the journal: one of the keymap's actions the panel
+/// handles, or a key for one of the panel's own choices.
+enum PanelKey {
+    Action(Action, usize),
+    Key(KeyCode),
+}
+
+struct Ui<'a> {
+    stdout: Stdout,
+    config: &'a Config,
+    dispatcher: KeyDispatcher,
+    /// The first smartlog line shown on screen.
+    scroll: usize,
+    /// A message shown on the last row of the screen, instead of the hint bar.
+    status: Option<String>,
+    /// When, and on which item, the last left click happened.
+    last_click: Option<(Instant, usize)>,
+    /// Lines shown in a box over the smartlog until a key is pressed, such as the help.
+    overlay: Option<Vec<String>>,
+    show_details: bool,
+    details: DetailsCache,
+    /// The commit at the first end of the range being folded.
+    fold_anchor: Option<String>,
+    /// What's drawn on the terminal, to redraw only what changes.
+    screen: Screen,
+    /// The rest of the smartlog, while `sl` is still printing it.
+    feed: Option<SmartlogFeed>,
+}
+impl<'a> Ui<'a> {
+    fn new(config: &'a Config) -> Self {
+        Self {
+            stdout: io::stdout(),
+            config,
+            dispatcher: KeyDispatcher::new(),
+            scroll: 0,
+            status: None,
+            last_click: None,
+            overlay: None,
+            show_details: true,
+            details: DetailsCache::new(),
+            fold_anchor: None,
+            screen: Screen::new(),
+            feed: None,
+        }
+    }
+
+    fn enter_screen(&mut self) {
+        terminal::enable_raw_mode().unwrap();
+        match self.config.height {
+            Height::Full => {
+                self.stdout.execute(EnterAlternateScreen).unwrap();
+                self.screen.invalidate();
+            }
+            _ => self.screen = Screen::inline(self.make_room()),
+        }
+        self.stdout.execute(Hide).unwrap();
+        // lines wrapping over the next row would be left there when only that line is redrawn
+        self.stdout.execute(DisableLineWrap).unwrap();
+        if self.config.mouse {
+            self.stdout.execute(EnableMouseCapture).unwrap();
+        }
+    }
+
+    fn leave_screen(&mut self) {
+        if self.config.mouse {
+            self.stdout.execute(DisableMouseCapture).unwrap();
+        }
+        self.stdout.execute(EnableLineWrap).unwrap();
+        self.stdout.execute(Show).unwrap();
+        match self.screen.inline_top() {
+            // leave the shell's output above as it was, and the cursor where the UI started
+            Some(top) => {
+                self.stdout.queue(MoveTo(0, top)).unwrap();
+                self.stdout.queue(Clear(ClearType::FromCursorDown)).unwrap();
+                self.stdout.flush().unwrap();
+            }
+            None => {
+                self.stdout.execute(LeaveAlternateScreen).unwrap();
+            }
+        }
+        terminal::disable_raw_mode().unwrap();
+    }
+
+    /// Make room for the UI below the cursor, scrolling the shell's output up if needed.
+    /// Returns the terminal row the UI starts at.
+    fn make_room(&mut self) -> u16 {
+        let (_, height) = self.size();
+        // start on a line of its own, after any output left without a newline
+        if cursor::position().is_ok_and(|(column, _)| column > 0) {
+            self.stdout.write_all(b"\r\n").unwrap();
+        }
+        self.stdout
+            .write_all("\n".repeat(height as usize - 1).as_bytes())
+            .unwrap();
+        self.stdout.flush().unwrap();
+        let (_, row) = cursor::position().unwrap_or((0, height - 1));
+        (row + 1).saturating_sub(height)
+    }
+
+    /// The columns and rows the UI is drawn in: the whole screen, or the height it's given
+    /// inline.
+    fn size(&self) -> (u16, u16) {
+        let (columns, rows) = terminal::size().unwrap_or((80, 24));
+        (columns, self.config.height.rows(rows))
+    }
+
+    /// Open `path` in the user's editor, handing the terminal over until it exits.
+    fn edit(&mut self, path: impl AsRef<std::ffi::OsStr>) -> io::Result<std::process::ExitStatus> {
+        self.leave_screen();
+        let status = editor_command(path).status();
+        self.enter_screen();
+        status
+    }
+
+    /// Reload the smartlog, e.g. after a command changed the repo.
+    /// The selection stays on the same commit if it's still there.
+    fn refresh(&mut self, smartlog: &mut SmartLog) {
+        self.reload(smartlog, true);
+    }
+
+    /// Reload the smartlog, keeping the selection on the same commit if `keep_selection`,
+    /// otherwise selecting the working copy.
+    /// Wait for the next terminal event, adding the items parsed meanwhile to the smartlog.
+    fn next_event(&mut self, smartlog: &mut SmartLog) -> Event {
+        while let Some(feed) = &self.feed {
+            if crossterm::event::poll(FEED_INTERVAL).unwrap() {
+                break;
+            }
+            match feed.ready_items() {
+                Some(items) if items.is_empty() => {}
+                Some(items) => {
+                    let selected = smartlog.get_selected_commit_hash().map(String::from);
+                    smartlog.extend(items);
+                    // Sapling's selection may only just have come
+                    if smartlog.get_selected_commit_hash() != selected.as_deref() {
+                        self.scroll_to_selection(smartlog);
                     }
+                    self.render(smartlog);
                 }
-                KeyCode::Up => {
-                    smartlog.move_up();
+                None => {
+                    if let Some(Err(err)) = self.feed.take().map(SmartlogFeed::finish) {
+                        self.show_error("Can't read the whole smartlog", &err.to_string());
+                        self.render(smartlog);
+                    }
                 }
-                KeyCode::Down => {
-                    smartlog.move_down();
+            }
+        }
+        crossterm::event::read().unwrap()
+    }
+
+    fn reload(&mut self, smartlog: &mut SmartLog, keep_selection: bool) {
+        match sl_smartlog(&self.config.smartlog) {
+            Ok(raw_smartlog) => {
+                let selected = smartlog.get_selected_commit_hash().map(String::from);
+                let marked: Vec<String> = smartlog
+                    .marked_hashes()
+                    .into_iter()
+                    .map(String::from)
+                    .collect();
+                *smartlog = SmartLog::new(&raw_smartlog);
+                self.feed = None;
+                if let Some(hash) = selected.filter(|_| keep_selection) {
+                    smartlog.select_commit_by_hash(&hash);
                 }
-                KeyCode::Char(' ') | KeyCode::Enter => {
-                    commit_hash = Some(smartlog.get_selected_commit_hash().unwrap());
-                    break 'terminal_ui;
+                let marked: Vec<&str> = marked.iter().map(String::as_str).collect();
+                smartlog.set_marks(&marked);
+                self.details.clear();
+                self.scroll_to_selection(smartlog);
+            }
+            Err(err) => self.show_error("Can't refresh the smartlog", &err.to_string()),
+        }
+    }
+
+    /// Select the clicked commit and scroll with the wheel.
+    /// A double-click on a commit returns the action to run on it.
+    fn handle_mouse(
+        &mut self,
+        smartlog: &mut SmartLog,
+        mouse_event: &MouseEvent,
+    ) -> Option<(Action, usize)> {
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.dispatcher.reset();
+                let top = self.screen.inline_top().unwrap_or(0);
+                let row = mouse_event.row.checked_sub(top)?;
+                if row as usize >= self.smartlog_height() {
+                    return None;
+                }
+                let line = self.scroll + row as usize;
+                let Some(item_idx) = smartlog.select_commit_at_line(line) else {
+                    self.last_click = None;
+                    return None;
+                };
+                let now = Instant::now();
+                let double_click = matches!(
+                    self.last_click,
+                    Some((time, idx)) if idx == item_idx && now - time <= DOUBLE_CLICK_DELAY
+                );
+                if double_click {
+                    self.last_click = None;
+                    return Some((Action::Select, 1));
+                }
+                self.last_click = Some((now, item_idx));
+            }
+            MouseEventKind::ScrollUp => {
+                self.scroll = self.scroll.saturating_sub(WHEEL_SCROLL_LINES);
+            }
+            MouseEventKind::ScrollDown => {
+                self.scroll += WHEEL_SCROLL_LINES;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn navigate(&mut self, smartlog: &mut SmartLog, action: Action, count: usize) {
+        let page = self.smartlog_height() as isize;
+        let count = count as isize;
+        match action {
+            Action::MoveUp => smartlog.move_by_commits(-count),
+            Action::MoveDown => smartlog.move_by_commits(count),
+            Action::HalfPageUp => smartlog.move_by_lines(-count * page / 2),
+            Action::HalfPageDown => smartlog.move_by_lines(count * page / 2),
+            Action::PageUp => smartlog.move_by_lines(-count * page),
+            Action::PageDown => smartlog.move_by_lines(count * page),
+            Action::MoveTop => smartlog.move_to_top(),
+            Action::MoveBottom => smartlog.move_to_bottom(),
+            Action::Select
+            | Action::EditMessage
+            | Action::Fold
+            | Action::ToggleMark
+            | Action::ClearMarks
+            | Action::Graft
+            | Action::Next
+            | Action::Prev
+            | Action::StackTop
+            | Action::StackBottom
+            | Action::Undo
+            | Action::Redo
+            | Action::Journal
+            | Action::CreateBookmark
+            | Action::MoveBookmark
+            | Action::DeleteBookmark
+            | Action::JumpToBookmark
+            | Action::ToggleDetails
+            | Action::Help
+            | Action::Quit => {}
+        }
+    }
+
+    /// Ask for confirmation before acting on the selected commit, if configured to.
+    fn confirm_select(&mut self, smartlog: &SmartLog) -> bool {
+        if !self.config.confirm {
+            return true;
+        }
+        let verb = match self.config.default_action {
+            DefaultAction::Goto => "Go to",
+            DefaultAction::Print => "Print",
+        };
+        let question = format!(
+            "{} {}? [y/N]",
+            verb,
+            smartlog.get_selected_commit_hash().unwrap()
+        );
+        if self.confirm(smartlog, &question) {
+            return true;
+        }
+        self.status = Some("Cancelled".to_string());
+        false
+    }
+
+    /// Run the configured action on the selected commit.
+    /// Returns the text to print if the UI should close, or `None` to keep it open.
+    fn run_default_action(&mut self, smartlog: &mut SmartLog) -> Option<String> {
+        let hash = smartlog.get_selected_commit_hash()?.to_string();
+        match self.config.default_action {
+            DefaultAction::Goto => {
+                let warning = smartlog
+                    .selected_commit()
+                    .and_then(|commit| commit.state.goto_warning());
+                if let Some(warning) = warning {
+                    let question = format!("{} {}. Go to it anyway? [y/N]", hash, warning);
+                    if !self.confirm(smartlog, &question) {
+                        self.status = Some("Cancelled".to_string());
+                        return None;
+                    }
+                }
+                self.goto(smartlog, &hash)
+            }
+            DefaultAction::Print => Some(format!("{}\n", hash)),
+        }
+    }
+
+    /// Go to `hash`, first asking what to do with uncommitted changes if there are any.
+    /// Returns the output of the commands run, or `None` if the goto was cancelled, failed, or
+    /// stopped with conflicts.
+    fn goto(&mut self, smartlog: &mut SmartLog, hash: &str) -> Option<String> {
+        let changes = match sl_status() {
+            Ok(changes) => changes,
+            Err(err) => {
+                self.show_error("Can't check the working copy", &err.to_string());
+                return None;
+            }
+        };
+
+        let mut mode = GotoMode::Check;
+        let mut shelve = false;
+        if !changes.is_empty() {
+            match self.choose_dirty_goto(smartlog, &changes) {
+                Some('m') => mode = GotoMode::Merge,
+                Some('c') => {
+                    let question = format!(
+                        "Discard {} uncommitted change(s)? This can't be undone. [y/N]",
+                        changes.len()
+                    );
+                    if !self.confirm(smartlog, &question) {
+                        self.status = Some("Cancelled".to_string());
+                        return None;
+                    }
+                    mode = GotoMode::Clean;
+                }
+                Some('s') => shelve = true,
+                _ => {
+                    self.status = Some("Cancelled".to_string());
+                    return None;
+                }
+            }
+        }
+
+        let mut output = String::new();
+        if shelve {
+            match sl_shelve() {
+                Ok(result) if result.status.success() => output.push_str(&output_text(&result)),
+                Ok(result) => {
+                    self.show_error("sl shelve failed", &output_text(&result));
+                    return None;
+                }
+                Err(err) => {
+                    self.show_error("sl shelve failed", &err.to_string());
+                    return None;
+                }
+            }
+        }
+
+        match sl_goto(hash, mode) {
+            Ok(result) if result.status.success() => output.push_str(&output_text(&result)),
+            result => {
+                if self.check_conflicts(smartlog) {
+                    return None;
+                }
+                let mut message = match result {
+                    Ok(result) => output_text(&result),
+                    Err(err) => err.to_string(),
+                };
+                if shelve {
+                    // put the changes back where they were
+                    if let Ok(result) = sl_unshelve() {
+                        message.push_str(&output_text(&result));
+                    }
+                }
+                self.show_error("sl goto failed", &message);
+                return None;
+            }
+        }
+
+        if shelve {
+            match sl_unshelve() {
+                Ok(result) if !result.status.success() && self.check_conflicts(smartlog) => {
+                    return None;
+                }
+                Ok(result) => output.push_str(&output_text(&result)),
+                Err(err) => output.push_str(&format!(
+                    "sl unshelve failed: {}\nYour changes are still shelved, see `sl shelve --list`.\n",
+                    err
+                )),
+            }
+        }
+        Some(output)
+    }
+
+    /// Show the `preview` of a command and ask `question`; if confirmed, `run` the command and
+    /// reload the smartlog.
+    fn run_with_preview(
+        &mut self,
+        smartlog: &mut SmartLog,
+        question: &str,
+        preview: io::Result<std::process::Output>,
+        run: impl FnOnce() -> io::Result<std::process::Output>,
+    ) {
+        let preview = match preview {
+            Ok(preview) if preview.status.success() => preview,
+            Ok(preview) => return self.show_error(question, &output_text(&preview)),
+            Err(err) => return self.show_error(question, &err.to_string()),
+        };
+        // the question goes first, a long preview is cut at the bottom of the screen
+        let mut lines = vec![format!("{} [y/n]", question), String::new()];
+        lines.extend(output_text(&preview).trim_end().lines().map(String::from));
+        if self.choose(smartlog, lines, &['y', 'n']) != Some('y') {
+            self.status = Some("Cancelled".to_string());
+            return;
+        }
+
+        match run() {
+            Ok(result) if result.status.success() => {
+                let output = output_text(&result);
+                self.status = output.trim_end().lines().last().map(String::from);
+            }
+            Ok(result) => self.show_error(question, &output_text(&result)),
+            Err(err) => self.show_error(question, &err.to_string()),
+        }
+        let error = self.overlay.take();
+        self.refresh(smartlog);
+        // an error from the command is more useful than one from the refresh
+        if error.is_some() {
+            self.overlay = error;
+        }
+    }
+
+    /// List recent working copy and bookmark moves, previewing the smartlog as it was after the
+    /// one under the cursor, until one is restored or the user goes back to the smartlog.
+    fn browse_journal(&mut self, smartlog: &mut SmartLog) {
+        let entries = match sl_journal(JOURNAL_LIMIT) {
+            Ok(entries) if entries.is_empty() => {
+                self.status = Some("The journal is empty".to_string());
+                return;
+            }
+            Ok(entries) => entries,
+            Err(err) => return self.show_error("Can't read the journal", &err.to_string()),
+        };
+        let mut previews: HashMap<String, Vec<String>> = HashMap::new();
+        let mut cursor = 0;
+        loop {
+            let entry = &entries[cursor];
+            let preview = match entry.node() {
+                Some(node) => previews
+                    .entry(node.to_string())
+                    .or_insert_with(|| match sl_smartlog_at(&self.config.smartlog, node) {
+                        Ok(lines) => lines,
+                        Err(err) => vec![format!(
+                            "Can't show the smartlog: {}",
+                            err.to_string().trim()
+                        )],
+                    })
+                    .clone(),
+                None => vec![format!("The bookmark {} was deleted", entry.name)],
+            };
+            self.render_journal(&entries, cursor, &preview, None);
+
+            match self.read_panel_key() {
+                PanelKey::Action(Action::MoveUp, count) => cursor = cursor.saturating_sub(count),
+                PanelKey::Action(Action::MoveDown, count) => {
+                    cursor = (cursor + count).min(entries.len() - 1)
+                }
+                PanelKey::Action(Action::Quit, _) => return,
+                PanelKey::Action(Action::Select, _) => {
+                    let question = format!("Restore {}? [y/N]", entry.summary());
+                    self.render_journal(&entries, cursor, &preview, Some(&question));
+                    let confirmed = loop {
+                        if let Event::Key(key_event) = crossterm::event::read().unwrap() {
+                            break matches!(key_event.code, KeyCode::Char('y' | 'Y'));
+                        }
+                    };
+                    if confirmed {
+                        let entry = entry.clone();
+                        self.restore_journal_entry(smartlog, &entry);
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Move the working copy or bookmark back where `entry` left it, then reload the smartlog.
+    fn restore_journal_entry(&mut self, smartlog: &mut SmartLog, entry: &JournalEntry) {
+        match (entry.bookmark_restore_args(), entry.node()) {
+            (Some(args), _) => match sl_run(&args) {
+                Ok(result) if result.status.success() => {
+                    self.status = Some(format!("Restored bookmark {}", entry.name));
+                }
+                Ok(result) => self.show_error("Can't restore the bookmark", &output_text(&result)),
+                Err(err) => self.show_error("Can't restore the bookmark", &err.to_string()),
+            },
+            (None, Some(node)) => {
+                if let Some(output) = self.goto(smartlog, node) {
+                    self.status = output.trim_end().lines().last().map(String::from);
+                }
+            }
+            (None, None) => return,
+        }
+        if self.overlay.is_none() {
+            self.refresh(smartlog);
+        }
+    }
+
+    /// Draw the journal entries above the smartlog preview of the one under the cursor.
+    /// The last row shows `question`, or the journal's keys.
+    fn render_journal(
+        &mut self,
+        entries: &[JournalEntry],
+        cursor: usize,
+        preview: &[String],
+        question: Option<&str>,
+    ) {
+        let (columns, rows) = self.size();
+        let (columns, rows) = (columns as usize, rows as usize);
+        // the list takes the top third, the preview the rest
+        let list_height = (rows.saturating_sub(3) / 3).max(1);
+        let first = cursor.saturating_sub(list_height - 1);
+
+        let mut frame = Frame::new(columns as u16, rows as u16);
+        frame.line(
+            0,
+            "Journal: recent working copy and bookmark moves, newest first",
+        );
+        for (row, (idx, entry)) in entries
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(list_height)
+            .enumerate()
+        {
+            let line: String = entry.summary().chars().take(columns).collect();
+            if idx == cursor {
+                frame.line(row + 1, format!("{}{}{}", REVERSE, line, RESET));
+            } else {
+                frame.line(row + 1, line);
+            }
+        }
+        let top = list_height + 1;
+        frame.line(top, format!("{}{}{}", DIM, "─".repeat(columns), RESET));
+        let preview_height = rows.saturating_sub(top + 2);
+        for (row, line) in preview.iter().take(preview_height).enumerate() {
+            frame.line(top + 1 + row, format!("{}{}", line, RESET));
+        }
+        let last_row = rows.saturating_sub(1);
+        match question {
+            Some(question) => frame.line(last_row, question),
+            None => {
+                let hints = [(Action::Select, "restore"), (Action::Quit, "back")];
+                let hints: Vec<String> = hints
+                    .iter()
+                    .filter_map(|(action, label)| self.action_hint(*action, label))
+                    .collect();
+                frame.line(last_row, format!("{}{}{}", DIM, hints.join("  "), RESET));
+            }
+        }
+        self.draw(frame);
+    }
+
+    /// Edit the selected commit's message in the editor, then apply it with `sl metaedit`.
+    fn edit_message(&mut self, smartlog: &mut SmartLog) {
+        let Some(hash) = smartlog.get_selected_commit_hash().map(String::from) else {
+            return;
+        };
+        let message = match sl_log(&hash, "{desc}\n") {
+            Ok(message) => message,
+            Err(err) => return self.show_error("Can't read the commit message", &err.to_string()),
+        };
+        let Some(file) = self.edit_in_temp_file(&format!("{}.txt", hash), &message, true) else {
+            return;
+        };
+        match sl_metaedit(&hash, file.path()) {
+            Ok(result) if result.status.success() => {
+                self.refresh(smartlog);
+                self.status = Some(format!("Updated the message of {}", hash));
+            }
+            Ok(result) => self.show_error("sl metaedit failed", &output_text(&result)),
+            Err(err) => self.show_error("sl metaedit failed", &err.to_string()),
+        }
+    }
+
+    /// Let the user edit `text` in a temp file named after `name`. Returns the file unless it's
+    /// empty, or unchanged when `require_change`, otherwise shows why not.
+    fn edit_in_temp_file(
+        &mut self,
+        name: &str,
+        text: &str,
+        require_change: bool,
+    ) -> Option<TempFile> {
+        let file = match TempFile::new(name, text) {
+            Ok(file) => file,
+            Err(err) => {
+                self.show_error("Can't create a temp file", &err.to_string());
+                return None;
+            }
+        };
+        match self.edit(file.path()) {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                self.status = Some(format!("Editor exited with {}, nothing changed", status));
+                return None;
+            }
+            Err(err) => {
+                self.show_error("Can't start the editor", &err.to_string());
+                return None;
+            }
+        }
+        match file.read() {
+            Ok(edited) if edited.trim().is_empty() => {
+                self.status = Some("Empty message, nothing changed".to_string());
+                None
+            }
+            Ok(edited) if require_change && edited.trim_end() == text.trim_end() => {
+                self.status = Some("Message unchanged".to_string());
+                None
+            }
+            Ok(_) => Some(file),
+            Err(err) => {
+                self.show_error("Can't read the edited file", &err.to_string());
+                None
+            }
+        }
+    }
+
+    /// Start a fold on the selected commit, or fold the range from the commit it was started on.
+    fn fold(&mut self, smartlog: &mut SmartLog) {
+        let Some(hash) = smartlog.get_selected_commit_hash().map(String::from) else {
+            return;
+        };
+        match self.fold_anchor.take() {
+            None => self.fold_anchor = Some(hash),
+            Some(anchor) => self.fold_range(smartlog, &anchor, &hash),
+        }
+    }
+
+    /// Fold the stack between two commits into one, with a message edited in the editor.
+    fn fold_range(&mut self, smartlog: &mut SmartLog, first: &str, second: &str) {
+        const TITLE: &str = "Can't fold these commits";
+        let range = match sl_log(&fold::revset(first, second), &fold::template()) {
+            Ok(output) => FoldRange::validate(RangeCommit::parse_list(&output)),
+            Err(err) => Err(err.to_string()),
+        };
+        let range = match range {
+            Ok(range) => range,
+            Err(err) => return self.show_error(TITLE, &err),
+        };
+
+        let revset = format!("{}::{}", range.bottom().node, range.top().node);
+        let message = match sl_log(&revset, "{desc}\n\n") {
+            Ok(message) => format!("{}\n", message.trim_end()),
+            Err(err) => return self.show_error(TITLE, &err.to_string()),
+        };
+        let Some(file) = self.edit_in_temp_file("fold-message.txt", &message, false) else {
+            return;
+        };
+
+        let mut args = range.fold_args();
+        args.push("-l".to_string());
+        args.push(file.path().to_string_lossy().to_string());
+        match sl_run(&args) {
+            Ok(result) if result.status.success() => {
+                self.refresh(smartlog);
+                self.status = Some(format!("Folded {} commits", range.commits.len()));
+            }
+            // rebasing the commits above the fold can stop with conflicts
+            _ if self.check_conflicts(smartlog) => {}
+            Ok(result) => self.show_error("sl fold failed", &output_text(&result)),
+            Err(err) => self.show_error("sl fold failed", &err.to_string()),
+        }
+    }
+
+    /// Graft the marked commits, or the selected one if none are marked, onto the working copy.
+    fn graft(&mut self, smartlog: &mut SmartLog) {
+        let mut hashes: Vec<String> = smartlog
+            .marked_hashes()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        if hashes.is_empty() {
+            hashes.extend(smartlog.get_selected_commit_hash().map(String::from));
+        }
+        if hashes.is_empty() {
+            return;
+        }
+        // the graph lists the newest commits first, graft the oldest first
+        hashes.reverse();
+        let question = format!(
+            "Graft {} onto the working copy? [y/N]",
+            match &hashes[..] {
+                [hash] => hash.clone(),
+                _ => format!("{} marked commits", hashes.len()),
+            }
+        );
+        if !self.confirm(smartlog, &question) {
+            self.status = Some("Cancelled".to_string());
+            return;
+        }
+
+        let hashes: Vec<&str> = hashes.iter().map(String::as_str).collect();
+        match sl_graft(&hashes) {
+            Ok(result) if result.status.success() => {
+                smartlog.clear_marks();
+                self.refresh(smartlog);
+                self.status = Some(format!("Grafted {} commit(s)", hashes.len()));
+            }
+            _ if self.check_conflicts(smartlog) => smartlog.clear_marks(),
+            Ok(result) => self.show_error("sl graft failed", &output_text(&result)),
+            Err(err) => self.show_error("sl graft failed", &err.to_string()),
+        }
+    }
+
+    /// Move the working copy along its stack with `sl next`/`sl prev`, letting the user pick
+    /// the way when the stack forks above it.
+    fn move_in_stack(&mut self, smartlog: &mut SmartLog, stack_move: StackMove) {
+        let command = if stack_move.goes_up() {
+            "sl next"
+        } else {
+            "sl prev"
+        };
+        let mut result = sl_stack_move(stack_move, None);
+        let ambiguous = matches!(
+            &result,
+            Ok(output) if !output.status.success() && is_ambiguous_next(&output_text(output))
+        );
+        if ambiguous && stack_move.goes_up() {
+            let Some(head) = self.pick_next_head(smartlog) else {
+                return;
+            };
+            result = sl_stack_move(stack_move, Some(&head));
+        }
+
+        match result {
+            Ok(output) if output.status.success() => {
+                self.reload(smartlog, false);
+                self.status = output_text(&output)
+                    .trim_end()
+                    .lines()
+                    .last()
+                    .map(String::from);
+            }
+            Ok(output) => self.show_error(&format!("{} failed", command), &output_text(&output)),
+            Err(err) => self.show_error(&format!("{} failed", command), &err.to_string()),
+        }
+    }
+
+    /// Ask which of the stack heads above the working copy to move towards.
+    fn pick_next_head(&mut self, smartlog: &SmartLog) -> Option<String> {
+        let heads = match sl_next_heads() {
+            Ok(heads) => heads,
+            Err(err) => {
+                self.show_error("Can't list the commits above", &err.to_string());
+                return None;
+            }
+        };
+        let items = heads
+            .iter()
+            .map(|(hash, title)| format!("{}  {}", hash, title))
+            .collect();
+        let Some(idx) = self.pick(smartlog, "The stack forks, move towards:", items) else {
+            self.status = Some("Cancelled".to_string());
+            return None;
+        };
+        Some(heads[idx].0.clone())
+    }
+
+    fn create_bookmark(&mut self, smartlog: &mut SmartLog) {
+        let Some(hash) = smartlog.get_selected_commit_hash().map(String::from) else {
+            return;
+        };
+        let Some(name) = self.input(smartlog, &format!("New bookmark on {}: ", hash)) else {
+            self.status = Some("Cancelled".to_string());
+            return;
+        };
+        if let Err(err) = validate_name(&name) {
+            self.status = Some(err);
+            return;
+        }
+        let result = sl_bookmark_create(&name, &hash);
+        self.finish_bookmark_command(smartlog, result, format!("Created {} on {}", name, hash));
+    }
+
+    fn move_bookmark(&mut self, smartlog: &mut SmartLog) {
+        let Some(hash) = smartlog.get_selected_commit_hash().map(String::from) else {
+            return;
+        };
+        let Some(bookmark) = self.pick_bookmark(smartlog, &format!("Move to {}", hash), false)
+        else {
+            return;
+        };
+        let result = sl_bookmark_move(&bookmark.name, &hash);
+        let message = format!("Moved {} to {}", bookmark.name, hash);
+        self.finish_bookmark_command(smartlog, result, message);
+    }
+
+    fn delete_bookmark(&mut self, smartlog: &mut SmartLog) {
+        let Some(bookmark) = self.pick_bookmark(smartlog, "Delete a bookmark", false) else {
+            return;
+        };
+        if !self.confirm(smartlog, &format!("Delete {}? [y/N]", bookmark.name)) {
+            self.status = Some("Cancelled".to_string());
+            return;
+        }
+        let result = sl_bookmark_delete(&bookmark.name);
+        let message = format!("Deleted {}", bookmark.name);
+        self.finish_bookmark_command(smartlog, result, message);
+    }
+
+    fn jump_to_bookmark(&mut self, smartlog: &mut SmartLog) {
+        let Some(bookmark) = self.pick_bookmark(smartlog, "Jump to a bookmark", true) else {
+            return;
+        };
+        if smartlog.select_commit_by_hash(&bookmark.node) {
+            self.scroll_to_selection(smartlog);
+        } else {
+            self.status = Some(format!("{} isn't in the smartlog", bookmark.name));
+        }
+    }
+
+    /// Show the outcome of a bookmark command, reloading the smartlog if it succeeded.
+    fn finish_bookmark_command(
+        &mut self,
+        smartlog: &mut SmartLog,
+        result: io::Result<std::process::Output>,
+        success_message: String,
+    ) {
+        match result {
+            Ok(output) if output.status.success() => {
+                self.refresh(smartlog);
+                self.status = Some(success_message);
+            }
+            Ok(output) => self.show_error("sl bookmark failed", &output_text(&output)),
+            Err(err) => self.show_error("sl bookmark failed", &err.to_string()),
+        }
+    }
+
+    /// Let the user pick a bookmark, including remote ones if `with_remote`.
+    fn pick_bookmark(
+        &mut self,
+        smartlog: &SmartLog,
+        title: &str,
+        with_remote: bool,
+    ) -> Option<Bookmark> {
+        let bookmarks: Vec<Bookmark> = match sl_bookmarks() {
+            Ok(bookmarks) => bookmarks
+                .into_iter()
+                .filter(|bookmark| with_remote || !bookmark.remote)
+                .collect(),
+            Err(err) => {
+                self.show_error("Can't list bookmarks", &err.to_string());
+                return None;
+            }
+        };
+        if bookmarks.is_empty() {
+            self.status = Some("No bookmarks".to_string());
+            return None;
+        }
+        let items = bookmarks
+            .iter()
+            .map(|bookmark| {
+                let node: String = bookmark.node.chars().take(9).collect();
+                format!("{}  {}", bookmark.name, node)
+            })
+            .collect();
+        let idx = self.pick(smartlog, title, items)?;
+        Some(bookmarks[idx].clone())
+    }
+
+    /// Let the user pick one of `items` with a fuzzy filter, in a box over the smartlog.
+    /// Returns the index of the picked item, or `None` if cancelled with escape.
+    fn pick(&mut self, smartlog: &SmartLog, title: &str, items: Vec<String>) -> Option<usize> {
+        let (_, rows) = self.size();
+        let list_height = (rows as usize / 2).max(1);
+        let mut picker = Picker::new(items);
+        let picked = loop {
+            let mut lines = vec![title.to_string(), format!("> {}", picker.query())];
+            let first = picker.cursor().saturating_sub(list_height - 1);
+            for (idx, item) in picker.matches().enumerate().skip(first).take(list_height) {
+                let style = if idx == picker.cursor() { REVERSE } else { "" };
+                let positions = fuzzy_positions(picker.query(), item).unwrap_or_default();
+                lines.push(format!(
+                    "{}{}{}",
+                    style,
+                    highlight(item, &positions, &self.config.colors.search, style),
+                    RESET
+                ));
+            }
+            if picker.match_count() == 0 {
+                lines.push(format!("{}No matches{}", DIM, RESET));
+            }
+            self.overlay = Some(lines);
+            self.render(smartlog);
+
+            let Event::Key(key_event) = crossterm::event::read().unwrap() else {
+                continue;
+            };
+            let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+            match key_event.code {
+                KeyCode::Esc => break None,
+                KeyCode::Char('c') if ctrl => break None,
+                KeyCode::Enter => break picker.selected(),
+                KeyCode::Up => picker.move_up(),
+                KeyCode::Char('p') if ctrl => picker.move_up(),
+                KeyCode::Down => picker.move_down(),
+                KeyCode::Char('n') if ctrl => picker.move_down(),
+                KeyCode::Backspace => picker.pop(),
+                KeyCode::Char(c) if !ctrl => picker.push(c),
+                _ => {}
+            }
+        };
+        self.overlay = None;
+        picked
+    }
+
+    /// Read a line of text typed on the status line. Escape cancels.
+    fn input(&mut self, smartlog: &SmartLog, prompt: &str) -> Option<String> {
+        let mut text = String::new();
+        let result = loop {
+            self.status = Some(format!("{}{}", prompt, text));
+            self.render(smartlog);
+            let Event::Key(key_event) = crossterm::event::read().unwrap() else {
+                continue;
+            };
+            let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+            match key_event.code {
+                KeyCode::Esc => break None,
+                KeyCode::Char('c') if ctrl => break None,
+                KeyCode::Enter => break Some(text),
+                KeyCode::Backspace => {
+                    text.pop();
                 }
+                KeyCode::Char(c) if !ctrl => text.push(c),
                 _ => {}
             }
+        };
+        self.status = None;
+        result
+    }
+
+    /// Open the conflict resolution panel if there are merge conflicts to resolve or an
+    /// interrupted operation to continue, then reload the smartlog.
+    /// Returns whether the panel was shown.
+    fn check_conflicts(&mut self, smartlog: &mut SmartLog) -> bool {
+        // outside of a merge the list is empty, and a failure to list means there's nothing to show
+        let files = sl_resolve_list().unwrap_or_default();
+        let operation = sl_unfinished_operation().ok().flatten();
+        let mut panel = ConflictPanel::new(operation, files);
+        if !panel.needs_resolution() {
+            return false;
+        }
+        self.resolve_conflicts(&mut panel);
+        self.refresh(smartlog);
+        true
+    }
+
+    /// Run the conflict resolution panel until everything is resolved and continued, the
+    /// operation is aborted, or the user goes back to the smartlog.
+    fn resolve_conflicts(&mut self, panel: &mut ConflictPanel) {
+        let mut message: Vec<String> = Vec::new();
+        loop {
+            self.render_conflicts(panel, &message);
+            let result = match self.read_panel_key() {
+                PanelKey::Action(Action::MoveUp, count) => {
+                    (0..count).for_each(|_| panel.move_up());
+                    continue;
+                }
+                PanelKey::Action(Action::MoveDown, count) => {
+                    (0..count).for_each(|_| panel.move_down());
+                    continue;
+                }
+                PanelKey::Action(Action::Quit, _) => return,
+                PanelKey::Action(Action::Select, _) | PanelKey::Key(KeyCode::Char('e')) => {
+                    let Some(file) = panel.selected() else {
+                        continue;
+                    };
+                    let path = file.path.clone();
+                    match self.edit(&path) {
+                        Ok(status) if status.success() => {
+                            message = vec![format!("Edited {}, press m to mark it resolved", path)];
+                        }
+                        Ok(status) => message = vec![format!("Editor exited with {}", status)],
+                        Err(err) => message = vec![format!("Can't start the editor: {}", err)],
+                    }
+                    continue;
+                }
+                PanelKey::Key(KeyCode::Char(c @ ('m' | 'u'))) => {
+                    let Some(file) = panel.selected() else {
+                        continue;
+                    };
+                    sl_resolve_mark(&file.path.clone(), c == 'm')
+                }
+                PanelKey::Key(KeyCode::Char('c')) => {
+                    if panel.unresolved_count() > 0 {
+                        message = vec![format!(
+                            "{} file(s) still unresolved",
+                            panel.unresolved_count()
+                        )];
+                        continue;
+                    }
+                    match panel.operation {
+                        Some(operation) if operation.can_continue() => sl_continue(),
+                        _ => return,
+                    }
+                }
+                PanelKey::Key(KeyCode::Char('a')) => {
+                    let Some(operation) = panel.operation else {
+                        continue;
+                    };
+                    if !self.confirm_in_panel(panel, &message, &operation.abort_args().join(" ")) {
+                        continue;
+                    }
+                    sl_abort(operation)
+                }
+                _ => continue,
+            };
+
+            message = match result {
+                Ok(output) => output_text(&output).lines().map(String::from).collect(),
+                Err(err) => vec![err.to_string()],
+            };
+            // continuing a rebase can stop again at the next commit with new conflicts
+            panel.operation = sl_unfinished_operation().ok().flatten();
+            match sl_resolve_list() {
+                Ok(files) => panel.update_files(files),
+                Err(err) => message.push(err.to_string()),
+            }
+            if !panel.needs_resolution() {
+                return;
+            }
+        }
+    }
+
+    /// Ask before aborting, on the last row of the conflict panel.
+    fn confirm_in_panel(&mut self, panel: &ConflictPanel, message: &[String], abort: &str) -> bool {
+        let mut message = message.to_vec();
+        message.push(String::new());
+        message.push(format!(
+            "Abort with `sl {}`? Changes made while resolving are lost. [y/N]",
+            abort
+        ));
+        self.render_conflicts(panel, &message);
+        loop {
+            if let Event::Key(key_event) = crossterm::event::read().unwrap() {
+                return matches!(key_event.code, KeyCode::Char('y') | KeyCode::Char('Y'));
+            }
+        }
+    }
+
+    /// Wait for a key in a panel. Moving, selecting and quitting go through the keymap like in
+    /// the smartlog, counts and key sequences included; other keys are the panel's to handle.
+    fn read_panel_key(&mut self) -> PanelKey {
+        loop {
+            let Event::Key(key_event) = crossterm::event::read().unwrap() else {
+                continue;
+            };
+            match self.dispatcher.feed(&self.config.keymap, &key_event) {
+                Dispatch::Action(
+                    action @ (Action::MoveUp | Action::MoveDown | Action::Select | Action::Quit),
+                    count,
+                ) => return PanelKey::Action(action, count),
+                Dispatch::Pending => continue,
+                Dispatch::Action(..) | Dispatch::Unbound => return PanelKey::Key(key_event.code),
+            }
+        }
+    }
+
+    /// Draw the conflicted files, the output of the last command, and the panel's keys.
+    fn render_conflicts(&mut self, panel: &ConflictPanel, message: &[String]) {
+        let (columns, rows) = self.size();
+        let (columns, rows) = (columns as usize, rows as usize);
+        let list_height = rows.saturating_sub(4 + message.len()).max(1);
+        let first = panel.cursor.saturating_sub(list_height - 1);
+
+        let mut lines = vec![panel.title(), String::new()];
+        for (idx, file) in panel.files.iter().enumerate().skip(first).take(list_height) {
+            let (status, color) = if file.resolved {
+                ("R", "\u{1b}[32m")
+            } else {
+                ("U", "\u{1b}[31m")
+            };
+            let line = format!("{}{}{} {}", color, status, RESET, file.path);
+            if idx == panel.cursor {
+                lines.push(format!("{}{}{}", REVERSE, line.replace(RESET, ""), RESET));
+            } else {
+                lines.push(line);
+            }
         }
-        render_smartlog(&mut stdout, smartlog);
+        if !message.is_empty() {
+            lines.push(String::new());
+            lines.extend(
+                message
+                    .iter()
+                    .map(|line| line.chars().take(columns).collect()),
+            );
+        }
+
+        let edit = match self.config.keymap.keys_for(Action::Select).first() {
+            Some(key) => format!("{}/e edit", key),
+            None => "e edit".to_string(),
+        };
+        let mut hints = vec![edit];
+        hints.extend(["m mark resolved", "u unmark", "c continue"].map(String::from));
+        if panel.operation.is_some() {
+            hints.push("a abort".to_string());
+        }
+        hints.extend(self.action_hint(Action::Quit, "back"));
+
+        let mut frame = Frame::new(columns as u16, rows as u16);
+        for (row, line) in lines.into_iter().take(rows.saturating_sub(1)).enumerate() {
+            frame.line(row, line);
+        }
+        let hints: String = hints.join("  ").chars().take(columns).collect();
+        frame.line(rows.saturating_sub(1), format!("{}{}{}", DIM, hints, RESET));
+        self.draw(frame);
+    }
+
+    /// Ask what to do with uncommitted changes before a goto.
+    fn choose_dirty_goto(&mut self, smartlog: &SmartLog, changes: &[String]) -> Option<char> {
+        const MAX_LISTED: usize = 10;
+        let mut lines = vec![format!(
+            "The working copy has {} uncommitted change(s):",
+            changes.len()
+        )];
+        lines.extend(
+            changes
+                .iter()
+                .take(MAX_LISTED)
+                .map(|change| format!("  {}", change)),
+        );
+        if changes.len() > MAX_LISTED {
+            lines.push(format!("  ... and {} more", changes.len() - MAX_LISTED));
+        }
+        lines.extend([
+            String::new(),
+            "m  goto --merge: bring the changes along".to_string(),
+            "c  goto --clean: discard the changes".to_string(),
+            "s  shelve the changes, goto, then unshelve them".to_string(),
+            "a  abort".to_string(),
+        ]);
+        self.choose(smartlog, lines, &['m', 'c', 's', 'a'])
+    }
+
+    /// Show `lines` in a box and wait for one of the `choices` keys. Escape chooses nothing.
+    fn choose(
+        &mut self,
+        smartlog: &SmartLog,
+        lines: Vec<String>,
+        choices: &[char],
+    ) -> Option<char> {
+        self.overlay = Some(lines);
+        self.render(smartlog);
+        let choice = loop {
+            if let Event::Key(key_event) = crossterm::event::read().unwrap() {
+                match key_event.code {
+                    KeyCode::Esc => break None,
+                    KeyCode::Char(c) if choices.contains(&c) => break Some(c),
+                    _ => {}
+                }
+            }
+        };
+        self.overlay = None;
+        choice
+    }
+
+    /// Show an error message in a box until a key is pressed.
+    fn show_error(&mut self, title: &str, message: &str) {
+        let mut lines = vec![title.to_string(), String::new()];
+        lines.extend(message.trim_end().lines().map(String::from));
+        lines.push(String::new());
+        lines.push(format!("{}Press any key to close{}", DIM, RESET));
+        self.overlay = Some(lines);
+    }
+
+    /// Show `question` on the status line and wait for a yes/no answer.
+    fn confirm(&mut self, smartlog: &SmartLog, question: &str) -> bool {
+        self.status = Some(question.to_string());
+        self.render(smartlog);
+        let answer = loop {
+            if let Event::Key(key_event) = crossterm::event::read().unwrap() {
+                break matches!(key_event.code, KeyCode::Char('y') | KeyCode::Char('Y'));
+            }
+        };
+        self.status = None;
+        answer
+    }
+
+    /// The number of rows available to the smartlog, keeping the last one for the status line.
+    fn smartlog_height(&self) -> usize {
+        let (_, rows) = self.size();
+        (rows as usize)
+            .saturating_sub(1 + self.details_height())
+            .max(1)
+    }
+
+    /// The number of rows taken by the detail panel: up to a third of the screen.
+    fn details_height(&self) -> usize {
+        if !self.show_details {
+            return 0;
+        }
+        let (_, rows) = self.size();
+        (rows as usize / 3).min(MAX_DETAILS_HEIGHT)
+    }
+
+    /// Draw the details of the selected commit between the smartlog and the status line.
+    fn render_details(&mut self, smartlog: &SmartLog, top: usize, frame: &mut Frame) {
+        let height = self.details_height();
+        if height == 0 {
+            return;
+        }
+        let (columns, _) = self.size();
+        let width = columns as usize;
+        let commit = smartlog.selected_commit();
+        // only the selected commit's details are fetched, once
+        let lines = match commit.and_then(|c| c.hash()) {
+            Some(hash) => match self.details.fetch(hash) {
+                Ok(Some(details)) => {
+                    details.panel_lines(commit.and_then(|c| c.ci_signal()), width, height - 1)
+                }
+                Ok(None) => vec![format!("No details for {}", hash)],
+                Err(err) => vec![format!("Can't get commit details: {}", err)],
+            },
+            None => Vec::new(),
+        };
+
+        frame.line(top, format!("{}{}{}", DIM, "─".repeat(width), RESET));
+        for (idx, line) in lines.iter().take(height - 1).enumerate() {
+            frame.line(top + 1 + idx, line.chars().take(width).collect::<String>());
+        }
+    }
+
+    /// Scroll just enough for the selected commit to be entirely on screen.
+    fn scroll_to_selection(&mut self, smartlog: &SmartLog) {
+        self.scroll = scroll_to(
+            smartlog.selected_line_range(),
+            self.scroll,
+            self.smartlog_height(),
+        );
+    }
+
+    fn render(&mut self, smartlog: &SmartLog) {
+        let height = self.smartlog_height();
+        self.scroll = self
+            .scroll
+            .min(smartlog.line_count().saturating_sub(height));
+
+        let (columns, rows) = self.size();
+        let mut frame = Frame::new(columns, rows);
+        // only the items on screen are styled, starting from the one the first row is part of
+        let (first_item, first_line) = match smartlog.item_index_at_line(self.scroll) {
+            Some(idx) => (idx, self.scroll - smartlog.item_line_starts()[idx]),
+            None => (smartlog.items().len(), 0),
+        };
+        let lines = smartlog.items()[first_item..]
+            .iter()
+            .flat_map(|item| self.render_item(item, smartlog, columns as usize))
+            .skip(first_line)
+            .take(height);
+        for (row, line) in lines.enumerate() {
+            frame.line(row, line);
+        }
+        self.render_details(smartlog, height, &mut frame);
+        let status_row = height + self.details_height();
+        match &self.status {
+            Some(status) => frame.line(status_row, status.as_str()),
+            None => frame.line(status_row, format!("{}{}{}", DIM, self.hint_bar(), RESET)),
+        }
+        if let Some(lines) = &self.overlay {
+            Self::render_overlay(lines, &mut frame);
+        }
+        self.draw(frame);
+    }
+
+    /// Draw `frame`, writing only what changed since the last one.
+    fn draw(&mut self, frame: Frame) {
+        let (_, rows) = terminal::size().unwrap_or((80, 24));
+        // a terminal made smaller pushes the bottom of an inline UI off the screen
+        if let Some(top) = self.screen.inline_top() {
+            let fitting_top = rows.saturating_sub(frame.height() as u16);
+            if top > fitting_top {
+                self.screen = Screen::inline(fitting_top);
+            }
+        }
+        self.screen.draw(frame, &mut self.stdout).unwrap();
     }
 
-    // Cleanup
-    stdout.execute(Show).unwrap();
-    stdout.execute(LeaveAlternateScreen).unwrap();
-    terminal::disable_raw_mode().unwrap();
+    /// The lines of a graph item, with the selection, marks and commit state drawn over them.
+    fn render_item(&self, item: &ItemType, smartlog: &SmartLog, width: usize) -> Vec<String> {
+        let ItemType::Commit(commit) = item else {
+            return item.to_string_vec();
+        };
+        let colors = &self.config.colors;
+        // Sapling's own highlight of the commit it selected is what we'd draw anyway
+        let sapling_selection = commit.selected
+            && commit.is_sapling_selected()
+            && colors.selection_style == SelectionStyle::Text
+            && colors.selection == SAPLING_SELECTION_COLOR;
 
-    commit_hash
+        let mut overlays = Vec::new();
+        if commit.selected && !sapling_selection {
+            overlays.push(match colors.selection_style {
+                SelectionStyle::Text => Overlay::Text(colors.selection.clone()),
+                SelectionStyle::Reverse => Overlay::Row(REVERSE.to_string()),
+                SelectionStyle::Background => Overlay::Row(background(&colors.selection)),
+            });
+        }
+        match commit.state {
+            CommitState::Obsolete => overlays.push(Overlay::Line(DIM.to_string())),
+            CommitState::Conflicted => overlays.push(Overlay::Line(UNDERLINE.to_string())),
+            CommitState::Normal | CommitState::WorkingCopy => {}
+        }
+        let mark = commit
+            .hash()
+            .filter(|hash| smartlog.is_marked(hash))
+            .and(commit.hash_block())
+            .map(|block| Overlay::Block(block, format!("{}{}", colors.mark, REVERSE)));
+
+        commit
+            .display_lines(sapling_selection)
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| match (idx, &mark) {
+                (0, Some(mark)) => {
+                    let mut overlays = overlays.clone();
+                    overlays.push(mark.clone());
+                    style::render(line, &overlays, width)
+                }
+                _ => style::render(line, &overlays, width),
+            })
+            .collect()
+    }
+
+    /// A one-line summary of the most common keys, e.g. `up/k up  down/j down  ? help`.
+    fn hint_bar(&self) -> String {
+        let (columns, _) = self.size();
+        let hints: Vec<String> = HINTS
+            .iter()
+            .filter_map(|(action, label)| self.action_hint(*action, label))
+            .collect();
+        hints.join("  ").chars().take(columns as usize).collect()
+    }
+
+    /// The first keys bound to `action` followed by `label`, e.g. `q/esc back`, unless it's unbound.
+    fn action_hint(&self, action: Action, label: &str) -> Option<String> {
+        let keys = self.config.keymap.keys_for(action);
+        if keys.is_empty() {
+            return None;
+        }
+        let keys: Vec<String> = keys.iter().take(2).map(|key| key.to_string()).collect();
+        Some(format!("{} {}", keys.join("/"), label))
+    }
+
+    /// Every action and its keys, grouped by category.
+    fn help_lines(&self) -> Vec<String> {
+        let sections = self.config.keymap.describe();
+        let key_column_width = sections
+            .iter()
+            .flat_map(|(_, actions)| actions.iter())
+            .map(|(_, keys)| Self::join_keys(keys).chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let mut lines = Vec::new();
+        for (category, actions) in sections {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(category.title().to_string());
+            for (action, keys) in actions {
+                lines.push(format!(
+                    "  {:width$}  {}",
+                    Self::join_keys(&keys),
+                    action.description(),
+                    width = key_column_width
+                ));
+            }
+        }
+        lines.push(String::new());
+        lines.push(format!("{}Press any key to close{}", DIM, RESET));
+        lines
+    }
+
+    /// Draw `lines` in a box over the middle of the screen.
+    fn render_overlay(lines: &[String], frame: &mut Frame) {
+        let (columns, rows) = (frame.columns(), frame.height() as u16);
+        let inner_width = lines
+            .iter()
+            .map(|line| strip_styles(line).chars().count())
+            .max()
+            .unwrap_or(0)
+            .min((columns as usize).saturating_sub(4));
+        let left = (columns as usize).saturating_sub(inner_width + 4) / 2;
+        let top = (rows as usize).saturating_sub(lines.len() + 2) / 2;
+
+        let border = "─".repeat(inner_width + 2);
+        let mut rows_to_draw = vec![format!("┌{}┐", border)];
+        for line in lines.iter().take((rows as usize).saturating_sub(2)) {
+            let visible = strip_styles(line);
+            let padding = inner_width.saturating_sub(visible.chars().count());
+            let line: String = if visible.chars().count() > inner_width {
+                visible.chars().take(inner_width).collect()
+            } else {
+                line.clone()
+            };
+            rows_to_draw.push(format!("│ {}{} │", line, " ".repeat(padding)));
+        }
+        rows_to_draw.push(format!("└{}┘", border));
+
+        for (idx, row) in rows_to_draw.iter().enumerate() {
+            frame.put(top + idx, left as u16, format!("{}{}", RESET, row));
+        }
+    }
+
+    fn join_keys(keys: &[KeySequence]) -> String {
+        let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+        keys.join(", ")
+    }
+}
+
+/// The scroll position closest to `scroll` that shows the `selection` lines on a screen of
+/// `height` lines.
+fn scroll_to(selection: Range<usize>, scroll: usize, height: usize) -> usize {
+    if selection.start < scroll {
+        selection.start
+    } else if selection.end > scroll + height {
+        selection.end.saturating_sub(height)
+    } else {
+        scroll
+    }
 }
 
-fn render_smartlog(stdout: &mut Stdout, smartlog: &SmartLog) {
-    stdout.queue(Clear(ClearType::All)).unwrap();
-    for (idx, line) in smartlog.to_string_vec().iter().enumerate() {
-        stdout.queue(MoveTo(0_u16, idx as u16)).unwrap();
-        print!("{}", *line);
+/// `text` with the characters at `positions` in the `highlight` style, on top of the `style`
+/// the rest of it is in.
+fn highlight(text: &str, positions: &[usize], highlight: &str, style: &str) -> String {
+    let mut highlighted = String::new();
+    for (idx, c) in text.chars().enumerate() {
+        if positions.contains(&idx) {
+            highlighted.push_str(highlight);
+            highlighted.push(c);
+            highlighted.push_str(RESET);
+            highlighted.push_str(style);
+        } else {
+            highlighted.push(c);
+        }
+    }
+    highlighted
+}
+
+/// The text of a line without its styles, including the configured colors the UI adds to it.
+fn strip_styles(line: &str) -> String {
+    line.ansi_parse()
+        .filter_map(|output| match output {
+            Output::TextBlock(text) => Some(text),
+            Output::Escape(_) => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A smartlog of `commits` two-line commits, with the working copy at `working_copy`.
+    fn tall_smartlog(commits: usize, working_copy: usize) -> SmartLog {
+        let lines: Vec<String> = (0..commits)
+            .flat_map(|commit| {
+                let node = if commit == working_copy { '@' } else { 'o' };
+                [
+                    format!("{}  {:09x}  Dec 08 at 09:46  royrothenberg", node, commit),
+                    format!("│  fix the parser, part {}", commit),
+                ]
+            })
+            .chain(["~".to_string()])
+            .collect();
+        SmartLog::new(&lines)
+    }
+
+    #[test]
+    fn test_highlight() {
+        let cyan = "\u{1b}[0;36m";
+        assert_eq!(
+            highlight("main", &[0, 2], cyan, REVERSE),
+            format!("{cyan}m{RESET}{REVERSE}a{cyan}i{RESET}{REVERSE}n")
+        );
+        assert_eq!(highlight("main", &[], cyan, ""), "main");
+    }
+
+    #[test]
+    fn test_scroll_to() {
+        // the working copy is near the bottom of a smartlog taller than the screen
+        let smartlog = tall_smartlog(30, 27);
+        assert_eq!(smartlog.selected_line_range(), 54..56);
+        let scroll = scroll_to(smartlog.selected_line_range(), 0, 20);
+        assert_eq!(scroll, 36);
+        assert!((scroll..scroll + 20).contains(&54) && (scroll..scroll + 20).contains(&55));
+
+        // already shown
+        assert_eq!(scroll_to(4..6, 0, 20), 0);
+        assert_eq!(scroll_to(4..6, 2, 20), 2);
+        // above the screen
+        assert_eq!(scroll_to(4..6, 10, 20), 4);
     }
-    stdout.flush().unwrap();
 }